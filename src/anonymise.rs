@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use crate::*;
+
+// FNV-1a, stable across platforms and Rust versions unlike the std hashers
+const FNV_OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+const FNV_PRIME: u64 = 0x100000001B3;
+
+// Shorter names are not replaced inside chat messages, they match too many words
+const MIN_CHAT_NAME_LENGTH: usize = 3;
+
+#[derive(Clone, Debug, Default)]
+pub struct AnonymiseOptions {
+    /// Mixed into every pseudonym. The same salt maps a name to the same pseudonym in every replay.
+    pub salt: String,
+    /// Also replace player names mentioned in chat and trigger chat messages
+    pub chat: bool
+}
+
+/// Maps player names to pseudonyms like `Player#1A2B3C4D`.
+pub struct Anonymiser {
+    options: AnonymiseOptions,
+    pseudonyms: HashMap<String, String>
+}
+
+impl Anonymiser {
+    pub fn new(options: &AnonymiseOptions) -> Anonymiser {
+        return Anonymiser {
+            options: options.clone(),
+            pseudonyms: HashMap::new()
+        };
+    }
+
+    /// Pseudonym of a name, only depending on the name and the salt.
+    pub fn pseudonym(&mut self, name: &str) -> String {
+        if name.is_empty() {
+            return String::new();
+        }
+        let salt = &self.options.salt;
+        return self.pseudonyms.entry(name.to_string()).or_insert_with(|| {
+            let mut hash = FNV_OFFSET_BASIS;
+            for byte in salt.bytes().chain([0xFF]).chain(name.bytes()) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+            format!("Player#{:08X}", (hash >> 32) as u32 ^ hash as u32)
+        }).clone();
+    }
+
+    /// All names replaced so far with their pseudonyms.
+    pub fn pseudonyms(&self) -> &HashMap<String, String> {
+        return &self.pseudonyms;
+    }
+
+    fn anonymise_player(&mut self, player: &mut ReplayPlayer) {
+        player.battle_tag = self.pseudonym(&player.battle_tag);
+        // Only the battle tag is known to be a name, the clan and the undecoded fields may identify the player as well
+        if let Some(reforged) = player.reforged.as_mut() {
+            reforged.battle_tag = self.pseudonym(&reforged.battle_tag);
+            reforged.clan.clear();
+            reforged.unknown_fields.clear();
+            reforged.other_records.clear();
+        }
+    }
+
+    /// Replaces the names in the PlayerRecords, the game creator name and the Reforged player metadata.
+    /// Other Reforged metadata, e.g. clans, is removed.
+    pub fn anonymise_summary(&mut self, summary: &mut ReplaySummary) {
+        for player in summary.players.values_mut() {
+            self.anonymise_player(player);
+        }
+        summary.game_creator_battle_tag = self.pseudonym(&summary.game_creator_battle_tag);
+        summary.reforged_records.clear();
+    }
+
+    // Known names in a message, battle tags as well as the name in front of the '#'
+    fn anonymise_text(&self, text: &str) -> String {
+        let mut names: Vec<(&str, &str)> = vec![];
+        for (name, pseudonym) in &self.pseudonyms {
+            names.push((name, pseudonym));
+            if let Some((short_name, _)) = name.split_once('#') {
+                names.push((short_name, pseudonym));
+            }
+        }
+        // Battle tags before the names they start with
+        names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+        let mut text = text.to_string();
+        for (name, pseudonym) in names.into_iter().filter(|(name, _)| name.len() >= MIN_CHAT_NAME_LENGTH) {
+            text = text.replace(name, pseudonym);
+        }
+        return text;
+    }
+
+    // Pseudonym of a name in a W3MMD `init pid` message, which may be the name in front of the '#' only
+    fn anonymise_w3mmd_name(&mut self, name: &str) -> String {
+        let known = self.pseudonyms.iter()
+            .find(|(battle_tag, _)| *battle_tag == name || battle_tag.split_once('#').is_some_and(|(short_name, _)| short_name == name))
+            .map(|(_, pseudonym)| pseudonym.clone());
+        return known.unwrap_or_else(|| self.pseudonym(name));
+    }
+
+    // Sync store keys carry W3MMD messages, `init pid <pid> <name>` names a player and other messages may contain names
+    fn anonymise_sync_store_key(&mut self, key: &str) -> String {
+        if let Some((pid, name)) = key.strip_prefix("init pid ").and_then(|rest| rest.split_once(' ')) {
+            let mut unescaped_name = String::new();
+            let mut escaped = false;
+            for c in name.chars() {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    _ => {
+                        unescaped_name.push(c);
+                        escaped = false;
+                    }
+                }
+            }
+            return format!("init pid {} {}", pid, self.anonymise_w3mmd_name(&unescaped_name));
+        }
+        return self.anonymise_text(key);
+    }
+
+    fn anonymise_action(&mut self, action: &mut Action) {
+        match &mut action.kind {
+            ActionKind::TriggerChat { message, .. } if self.options.chat => *message = self.anonymise_text(message),
+            ActionKind::SyncStoreInteger { key, .. }
+            | ActionKind::SyncStoreUnknown6C { key, .. }
+            | ActionKind::SyncStoreUnknown6D { key, .. } => *key = self.anonymise_sync_store_key(key),
+            _ => {}
+        }
+    }
+
+    /// Replaces the names known from [`Anonymiser::anonymise_summary`] in the keys of sync store actions,
+    /// e.g. W3MMD `init pid` messages, and in chat and trigger chat messages if [`AnonymiseOptions::chat`] is set.
+    pub fn anonymise_record(&mut self, record: &mut ReplayRecord) {
+        match record {
+            ReplayRecord::Chat(message) if self.options.chat => message.message = self.anonymise_text(&message.message),
+            ReplayRecord::TimeSlot(time_slot) => {
+                for action in time_slot.command_blocks.iter_mut().flat_map(|b| b.actions.iter_mut()) {
+                    self.anonymise_action(action);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Same as [`Anonymiser::anonymise_summary`] and [`Anonymiser::anonymise_record`] for a parsed replay.
+    /// The raw records still hold the original names, so they are removed.
+    pub fn anonymise_replay(&mut self, replay: &mut Replay) {
+        for player in replay.players.values_mut() {
+            self.anonymise_player(player);
+        }
+        replay.metadata.game_creator_battle_tag = self.pseudonym(&replay.metadata.game_creator_battle_tag);
+        replay.reforged_records.clear();
+        replay.raw = None;
+        for action in replay.actions.iter_mut() {
+            self.anonymise_action(action);
+        }
+        if self.options.chat {
+            for message in replay.chat.iter_mut() {
+                message.message = self.anonymise_text(&message.message);
+            }
+        }
+    }
+}
+
+/// Reads a replay and writes a copy with all player names replaced by pseudonyms.
+pub fn anonymise<R: Read, W: Write>(reader: R, writer: W, options: &AnonymiseOptions) -> Result<Anonymiser, ReplayError> {
+    let mut anonymiser = Anonymiser::new(options);
+    let mut decoder = ReplayDecoder::new(reader)?;
+    anonymiser.anonymise_summary(decoder.summary_mut());
+
+    let mut replay_writer = ReplayWriter::new(decoder.summary())?;
+    while let Some(mut record) = decoder.next_record()? {
+        anonymiser.anonymise_record(&mut record);
+        replay_writer.write_record(&record)?;
+    }
+    replay_writer.finish(writer)?;
+    return Ok(anonymiser);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{chat, replay, summary, time_slot, write};
+
+    fn sync_store(message_number: usize, key: &str) -> ActionKind {
+        return ActionKind::SyncStoreInteger {
+            filename: String::from("MMD.Dat"),
+            mission_key: format!("val:{}", message_number),
+            key: key.to_string(),
+            value: 0
+        };
+    }
+
+    fn records() -> Vec<ReplayRecord> {
+        return vec![
+            chat(1, ChatChannel::Lobby, "hi Bob"),
+            time_slot(100, 1, vec![
+                sync_store(0, "init pid 0 Alice#123"),
+                sync_store(1, "init pid 1 Bob"),
+                ActionKind::TriggerChat { unknown_a: 0, unknown_b: 0, message: String::from("-kick Bob#456") }
+            ]),
+            time_slot(100, 2, vec![
+                ActionKind::SyncStoreUnknown6D {
+                    filename: String::from("MMD.Dat"),
+                    mission_key: String::from("val:2"),
+                    key: String::from("Custom Alice#123\\ wins"),
+                    value: 0
+                }
+            ]),
+            chat(2, ChatChannel::All, "gg Alice")
+        ];
+    }
+
+    #[test]
+    fn no_names_remain_in_the_written_replay() {
+        let bytes = write(&summary(&["Alice#123", "Bob#456"]), &records());
+        let mut anonymised: Vec<u8> = vec![];
+        let options = AnonymiseOptions { salt: String::from("salt"), chat: true };
+        anonymise(bytes.as_slice(), &mut anonymised, &options).unwrap();
+
+        let replay = Replay::try_from_bytes_with_options(&anonymised, &ParseOptions { keep_raw_records: true, ..Default::default() }).unwrap();
+        let data = replay.raw.unwrap().data();
+        for name in ["Alice", "Bob"] {
+            assert!(!data.windows(name.len()).any(|w| w == name.as_bytes()), "{} found", name);
+        }
+    }
+
+    #[test]
+    fn sync_store_keys_are_anonymised_without_chat() {
+        let mut replay = replay(&summary(&["Alice#123", "Bob#456"]), &records());
+        let mut anonymiser = Anonymiser::new(&AnonymiseOptions::default());
+        anonymiser.anonymise_replay(&mut replay);
+
+        let alice = anonymiser.pseudonyms()["Alice#123"].clone();
+        let bob = anonymiser.pseudonyms()["Bob#456"].clone();
+        let keys: Vec<&str> = replay.actions.iter().filter_map(|action| match &action.kind {
+            ActionKind::SyncStoreInteger { key, .. } | ActionKind::SyncStoreUnknown6D { key, .. } => Some(key.as_str()),
+            _ => None
+        }).collect();
+        assert_eq!(keys, [format!("init pid 0 {}", alice), format!("init pid 1 {}", bob), format!("Custom {}\\ wins", alice)]);
+        assert_eq!(replay.players[&1].battle_tag, alice);
+        // Chat is only anonymised with AnonymiseOptions::chat
+        assert!(replay.chat.iter().any(|message| message.message == "gg Alice"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{ActionType, Replay};
+
+const MINUTE_MS: u64 = 60 * 1000;
+
+#[derive(Default, Clone, Debug)]
+pub struct ApmOptions {
+    /// Do not count selection and control group (hotkey) actions
+    pub exclude_selection: bool
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerApm {
+    pub player_id: u8,
+    pub actions: u32,
+    pub apm: f64,
+    /// Number of actions in every started minute of game time until the player left
+    pub per_minute: Vec<u32>
+}
+
+impl ActionType {
+    /// Selection, subgroup and control group actions.
+    pub fn is_selection(&self) -> bool {
+        return matches!(self,
+            ActionType::CHANGE_SELECTION |
+            ActionType::GROUP_ASSIGN |
+            ActionType::GROUP_SELECT |
+            ActionType::SELECT_SUBGROUP |
+            ActionType::PRE_SUBSELECTION);
+    }
+}
+
+impl Replay {
+    /// Actions per minute of every player, measured in game time until `ReplayPlayer::left_at`.
+    pub fn apm(&self, options: &ApmOptions) -> HashMap<u8, PlayerApm> {
+        let mut stats: HashMap<u8, PlayerApm> = HashMap::new();
+        for (player_id, player) in &self.players {
+            let minutes = (self.game_time(player.left_at) / MINUTE_MS + 1) as usize;
+            stats.insert(*player_id, PlayerApm {
+                player_id: *player_id,
+                actions: 0,
+                apm: 0.0,
+                per_minute: vec![0; minutes]
+            });
+        }
+
+        for action in &self.actions {
+            let action_type = action.action_type();
+            // Sent by the game itself ahead of every subgroup selection
+            if action_type == ActionType::PRE_SUBSELECTION {
+                continue;
+            }
+            if options.exclude_selection && action_type.is_selection() {
+                continue;
+            }
+            let Some(player_stats) = stats.get_mut(&action.player_id) else {
+                continue;
+            };
+            let Some(bucket) = player_stats.per_minute.get_mut((self.game_time(action.timestamp) / MINUTE_MS) as usize) else {
+                continue;
+            };
+            *bucket += 1;
+            player_stats.actions += 1;
+        }
+
+        for player_stats in stats.values_mut() {
+            let game_time = self.game_time(self.players[&player_stats.player_id].left_at);
+            if game_time > 0 {
+                player_stats.apm = player_stats.actions as f64 * MINUTE_MS as f64 / game_time as f64;
+            }
+        }
+        return stats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ActionKind;
+    use crate::testing::{replay, summary, time_slot};
+
+    // The game starts after 50 seconds of loading
+    fn test_replay() -> Replay {
+        let group_select = || ActionKind::GroupSelect { group: 1, unknown: 3 };
+        return replay(&summary(&["Alice#123", "Bob#456"]), &[
+            time_slot(50000, 1, vec![]),
+            time_slot(100, 1, vec![ActionKind::EscPressed]),
+            time_slot(29900, 2, vec![ActionKind::EscPressed]),
+            time_slot(40000, 2, vec![ActionKind::PreSubselection, ActionKind::EscPressed, group_select()])
+        ]);
+    }
+
+    #[test]
+    fn minutes_of_game_time() {
+        let replay = test_replay();
+        assert_eq!(replay.game_start, 50000);
+        let apm = replay.apm(&ApmOptions::default());
+        assert_eq!(apm[&1].per_minute, [1]);
+        assert_eq!(apm[&1].apm, 600.0);
+        // Actions at 30 and 70 seconds of game time, the subselection is not counted
+        assert_eq!(apm[&2].per_minute, [1, 2]);
+        assert_eq!(apm[&2].actions, 3);
+        assert_eq!(apm[&2].apm, 3.0 * 60.0 / 70.0);
+    }
+
+    #[test]
+    fn selection_excluded() {
+        let apm = test_replay().apm(&ApmOptions { exclude_selection: true });
+        assert_eq!(apm[&2].per_minute, [1, 1]);
+        assert_eq!(apm[&2].actions, 2);
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::Read;
+use flate2::{Decompress, FlushDecompress};
+use log::{info, warn};
+use crate::{header, parse_dword, ParseOptions, ReplayError, ReplayHeader};
+
+// Inflated size of the data blocks, the last block is padded with zeros
+pub(crate) const BLOCK_SIZE: usize = 8192;
+
+// Reads and inflates the data blocks following the header one at a time
+pub(crate) struct BlockReader<R: Read> {
+    reader: R,
+    num_data_blocks: u32,
+    block: u32,
+    offset: u64,
+    verify_checksums: bool,
+    finished: bool
+}
+
+impl<R: Read> BlockReader<R> {
+    pub(crate) fn new(reader: R, header: &ReplayHeader, options: &ParseOptions) -> BlockReader<R> {
+        info!("Total data blocks: {:?}", header.num_data_blocks);
+        return BlockReader {
+            reader,
+            num_data_blocks: header.num_data_blocks,
+            block: 0,
+            offset: header.total_length() as u64,
+            verify_checksums: options.verify_checksums,
+            finished: header.num_data_blocks == 0
+        };
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        return self.finished;
+    }
+
+    pub(crate) fn next_block(&mut self) -> Result<Option<Vec<u8>>, ReplayError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        // 3.0 [Data block header]
+        let mut block_header: [u8; 12] = [0; 12];
+        if self.reader.read_exact(&mut block_header).is_err() {
+            warn!("Replay ended after {:?} of {:?} data blocks.", self.block, self.num_data_blocks);
+            self.finished = true;
+            return Ok(None);
+        }
+
+        let block_data_length = parse_dword(&block_header[0..4]);
+        let block_data_length_inflated = parse_dword(&block_header[4..8]);
+        let crc = parse_dword(&block_header[8..12]);
+        info!("Block at offset {:#06x} ({:?}) length: {:?} / inflated: {:?} / crc: {:#010x}", self.offset, self.offset, block_data_length, block_data_length_inflated, crc);
+
+        let truncated = ReplayError::TruncatedBlock { block: self.block, offset: self.offset };
+        let mut block_data: Vec<u8> = vec![];
+        match self.reader.by_ref().take(block_data_length as u64).read_to_end(&mut block_data) {
+            Ok(n) if n == block_data_length as usize => {},
+            _ => return Err(truncated)
+        }
+        info!("Read datablock of length {:?}.", block_data_length);
+
+        if self.verify_checksums {
+            let actual = header::block_checksum(&block_header, &block_data);
+            if actual != crc {
+                return Err(ReplayError::BlockChecksumMismatch { block: self.block, offset: self.offset, expected: crc, actual });
+            }
+        }
+
+        // 4.0 [Decompressed data]
+        let mut decoder = Decompress::new(true);
+        // One byte more than declared, so data inflating past the declared size is noticed
+        let mut out: Vec<u8> = Vec::with_capacity((block_data_length_inflated as usize).min(BLOCK_SIZE) + 1);
+        decoder.decompress_vec(&block_data, &mut out, FlushDecompress::Sync)
+            .map_err(|e| ReplayError::Decompression { block: self.block, source: e })?;
+        info!("Decompressed block length: {:?} / begins with {:?}", out.len(), &out[..out.len().min(8)]);
+        if out.len() != block_data_length_inflated as usize {
+            return Err(ReplayError::InflatedLengthMismatch { block: self.block, offset: self.offset, expected: block_data_length_inflated, actual: out.len() });
+        }
+
+        self.offset += block_data_length as u64 + 12;
+        self.block += 1;
+        self.finished = self.block >= self.num_data_blocks;
+        return Ok(Some(out));
+    }
+}
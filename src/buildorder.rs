@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{ActionKind, ItemId, ObjectCategory, Replay};
+
+#[derive(Serialize, Debug)]
+pub struct BuildOrderEntry {
+    pub timestamp: u64,
+    pub item_id: String,
+    pub category: ObjectCategory
+}
+
+impl Replay {
+    /// Units trained, buildings placed, upgrades researched, heroes summoned and items bought by every player.
+    ///
+    /// The replay only records the orders, so entries which were cancelled or could not be afforded are included.
+    pub fn build_order(&self) -> HashMap<u8, Vec<BuildOrderEntry>> {
+        let mut build_orders: HashMap<u8, Vec<BuildOrderEntry>> = HashMap::new();
+        for action in &self.actions {
+            let item_id = match &action.kind {
+                ActionKind::AbilityBasic { item_id, .. } |
+                ActionKind::AbilityWithTargetLocation { item_id, .. } |
+                ActionKind::AbilityWithTargetObject { item_id, .. } => item_id,
+                _ => continue
+            };
+            let ItemId::FourCC(fourcc) = item_id else {
+                continue;
+            };
+            let category = item_id.category();
+            if matches!(category, ObjectCategory::ABILITY | ObjectCategory::UNKNOWN) {
+                continue;
+            }
+            build_orders.entry(action.player_id).or_default().push(BuildOrderEntry {
+                timestamp: action.timestamp,
+                item_id: fourcc.clone(),
+                category
+            });
+        }
+        return build_orders;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapLocation, ObjectIDs};
+    use crate::testing::{replay, summary, time_slot};
+
+    fn fourcc(id: &str) -> ItemId {
+        return ItemId::FourCC(id.to_string());
+    }
+
+    fn train(id: &str) -> ActionKind {
+        return ActionKind::AbilityBasic { flags: 0, item_id: fourcc(id), unknown_a: 0, unknown_b: 0 };
+    }
+
+    #[test]
+    fn fourcc_classification() {
+        let categories: Vec<ObjectCategory> = ["hfoo", "hbar", "Rhme", "Rxyz", "Hpal", "stwp", "AHbz", "xxxx", "Rhm"]
+            .iter().map(|id| fourcc(id).category()).collect();
+        assert_eq!(categories, [
+            ObjectCategory::UNIT,
+            ObjectCategory::BUILDING,
+            ObjectCategory::UPGRADE,
+            // Unknown upgrades are recognised by their prefix
+            ObjectCategory::UPGRADE,
+            ObjectCategory::HERO,
+            ObjectCategory::ITEM,
+            ObjectCategory::ABILITY,
+            ObjectCategory::UNKNOWN,
+            ObjectCategory::UNKNOWN
+        ]);
+        assert_eq!(ItemId::Order(0xD0003).category(), ObjectCategory::UNKNOWN);
+    }
+
+    #[test]
+    fn build_order() {
+        let location = || MapLocation { x: 0.0, y: 0.0 };
+        let replay = replay(&summary(&["Alice#123", "Bob#456"]), &[
+            time_slot(100, 1, vec![
+                train("hpea"),
+                ActionKind::AbilityWithTargetLocation { flags: 0, item_id: fourcc("hbar"), unknown_a: 0, unknown_b: 0, location: location() }
+            ]),
+            time_slot(100, 2, vec![
+                // Orders and abilities are no entries
+                ActionKind::AbilityBasic { flags: 0, item_id: ItemId::Order(0xD0012), unknown_a: 0, unknown_b: 0 },
+                train("AHbz"),
+                train("xxxx"),
+                ActionKind::AbilityWithTargetObject {
+                    flags: 0,
+                    item_id: fourcc("Rhme"),
+                    unknown_a: 0,
+                    unknown_b: 0,
+                    location: location(),
+                    target: ObjectIDs { id1: 1, id2: 1 }
+                }
+            ]),
+            time_slot(100, 1, vec![train("Hpal"), train("stwp")])
+        ]);
+        let build_order = replay.build_order();
+        let entries = |player_id: u8| -> Vec<(u64, &str, ObjectCategory)> {
+            build_order[&player_id].iter().map(|e| (e.timestamp, e.item_id.as_str(), e.category)).collect()
+        };
+        assert_eq!(entries(1), [
+            (100, "hpea", ObjectCategory::UNIT),
+            (100, "hbar", ObjectCategory::BUILDING),
+            (300, "Hpal", ObjectCategory::HERO),
+            (300, "stwp", ObjectCategory::ITEM)
+        ]);
+        assert_eq!(entries(2), [(200, "Rhme", ObjectCategory::UPGRADE)]);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use serde::Serialize;
+use crate::*;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum MomentKind {
+    HERO_PICKED,
+    HERO_REVIVED,
+    HERO_MAX_LEVEL,
+    GAME_PAUSED,
+    PLAYER_LEFT,
+    W3MMD_EVENT
+}
+
+/// An interesting moment of the game, e.g. to choose the range of a [`clip`].
+#[derive(Serialize, Debug, Clone)]
+pub struct Moment {
+    pub timestamp: u64,
+    /// Timestamp relative to the game start, as expected by [`clip`]
+    pub game_time: u64,
+    pub kind: MomentKind,
+    pub player_id: Option<u8>
+}
+
+impl Replay {
+    /// Hero picks and revivals, pauses, leaves and W3MMD events, ordered by time.
+    pub fn moments(&self) -> Vec<Moment> {
+        let mut moments: Vec<(u64, MomentKind, Option<u8>)> = vec![];
+        for (player_id, heroes) in self.heroes() {
+            for hero in heroes {
+                if let Some(picked_at) = hero.picked_at {
+                    moments.push((picked_at, MomentKind::HERO_PICKED, Some(player_id)));
+                }
+                for revived_at in hero.revivals {
+                    moments.push((revived_at, MomentKind::HERO_REVIVED, Some(player_id)));
+                }
+                if let Some(skill) = hero.skills.iter().find(|s| s.level == MAX_HERO_LEVEL) {
+                    moments.push((skill.timestamp, MomentKind::HERO_MAX_LEVEL, Some(player_id)));
+                }
+            }
+        }
+        for action in self.actions.iter().filter(|a| matches!(a.kind, ActionKind::Pause)) {
+            moments.push((action.timestamp, MomentKind::GAME_PAUSED, Some(action.player_id)));
+        }
+        for leave in &self.leaves {
+            moments.push((leave.timestamp, MomentKind::PLAYER_LEFT, Some(leave.player_id)));
+        }
+        if let Some(w3mmd) = self.w3mmd() {
+            for event in w3mmd.events {
+                moments.push((event.timestamp, MomentKind::W3MMD_EVENT, None));
+            }
+        }
+        moments.sort_by_key(|(timestamp, _, player_id)| (*timestamp, *player_id));
+        return moments.into_iter().map(|(timestamp, kind, player_id)| Moment {
+            timestamp,
+            game_time: self.game_time(timestamp),
+            kind,
+            player_id
+        }).collect();
+    }
+}
+
+/// Writes a copy of a replay which stops at `end`, in milliseconds of game time.
+///
+/// The game has to simulate every TimeSlot up to `end`, so they are all kept and the replay is watched from `start` by fast forwarding.
+/// Chat outside of `start` - `end` is dropped, except for the lobby chat.
+/// Players still in the game at `end` leave there, the player of the 4.1 PlayerRecord last, which ends the replay.
+pub fn clip<R: Read, W: Write>(reader: R, writer: W, start: u64, end: u64) -> Result<(), ReplayError> {
+    if start > end {
+        return Err(ReplayError::InvalidClipRange { start, end });
+    }
+    let mut decoder = ReplayDecoder::new(reader)?;
+    let mut replay_writer = ReplayWriter::new(decoder.summary())?;
+    let mut left_players: HashSet<u8> = HashSet::new();
+    let mut last_timestamp = 0u64;
+    let mut clipped = false;
+
+    while let Some(record) = decoder.next_record()? {
+        let game_time = decoder.game_start().map_or(0, |game_start| decoder.timestamp().saturating_sub(game_start));
+        match &record {
+            ReplayRecord::TimeSlot(_) if game_time > end => {
+                clipped = true;
+                break;
+            },
+            ReplayRecord::TimeSlot(time_slot) => last_timestamp = time_slot.timestamp,
+            ReplayRecord::Chat(message) if message.channel != ChatChannel::Lobby && !(start..=end).contains(&game_time) => continue,
+            ReplayRecord::LeaveGame(leave) => {
+                left_players.insert(leave.player_id);
+            },
+            _ => {}
+        }
+        replay_writer.write_record(&record)?;
+    }
+
+    // Synthetic end
+    if clipped {
+        let summary = decoder.summary();
+        let mut remaining_players: Vec<u8> = summary.players.keys().filter(|id| !left_players.contains(id)).copied().collect();
+        remaining_players.sort_by_key(|id| (*id == summary.record_player_id, *id));
+        for player_id in remaining_players {
+            let reason = match player_id == summary.record_player_id {
+                true => LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME,
+                false => LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME
+            };
+            replay_writer.write_record(&ReplayRecord::LeaveGame(PlayerLeave {
+                player_id,
+                reason,
+                result: LeaveResult::LEFT as u32,
+                unknown: 0,
+                timestamp: last_timestamp
+            }))?;
+        }
+    }
+
+    replay_writer.finish(writer)?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{chat, leave, summary, time_slot, write};
+
+    // The game starts after a second of loading, game times of the TimeSlots are 100, 1100, 2100 and 3100
+    fn bytes() -> Vec<u8> {
+        return write(&summary(&["Alice#1", "Bob#2", "Carol#3"]), &[
+            chat(1, ChatChannel::Lobby, "gl"),
+            time_slot(1000, 1, vec![]),
+            time_slot(100, 1, vec![ActionKind::EscPressed]),
+            chat(2, ChatChannel::All, "early"),
+            time_slot(1000, 2, vec![ActionKind::EscPressed]),
+            chat(1, ChatChannel::All, "inside"),
+            leave(3, LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME, LeaveResult::LEFT),
+            time_slot(1000, 1, vec![ActionKind::EscPressed]),
+            chat(2, ChatChannel::All, "late"),
+            time_slot(1000, 2, vec![ActionKind::EscPressed])
+        ]);
+    }
+
+    #[test]
+    fn clip_ends_the_game() {
+        let mut clipped: Vec<u8> = vec![];
+        clip(bytes().as_slice(), &mut clipped, 1000, 2000).unwrap();
+        let replay = Replay::try_from_bytes(&clipped).unwrap();
+
+        assert_eq!(replay.game_start, 1000);
+        let game_times: Vec<u64> = replay.time_slots.iter().map(|t| replay.game_time(t.timestamp)).collect();
+        assert_eq!(game_times, [0, 100, 1100]);
+        let chat: Vec<&str> = replay.chat.iter().map(|m| m.message.as_str()).collect();
+        assert_eq!(chat, ["gl", "inside"]);
+        // The remaining players leave at the last TimeSlot, the record player last
+        let leaves: Vec<(u8, LeaveReason, u64)> = replay.leaves.iter().map(|l| (l.player_id, l.reason, l.timestamp)).collect();
+        assert_eq!(leaves, [
+            (3, LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME, 2100),
+            (2, LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME, 2100),
+            (1, LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME, 2100)
+        ]);
+        assert_eq!(replay.metadata.saving_player_id, 1);
+    }
+
+    #[test]
+    fn start_after_end() {
+        let result = clip(bytes().as_slice(), &mut vec![], 2000, 1000);
+        assert!(matches!(result, Err(ReplayError::InvalidClipRange { start: 2000, end: 1000 })));
+    }
+}
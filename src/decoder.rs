@@ -0,0 +1,910 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use log::{info, warn};
+use num_traits::FromPrimitive;
+use crate::*;
+use crate::blocks::BlockReader;
+use crate::reforged::{decode_protobuf, PLAYER_PROFILE_SUBTYPE};
+
+// Decoding result of a single record in the ReplayData
+enum Decoded {
+    Record(ReplayRecord),
+    End
+}
+
+/// Streaming decoder which inflates data blocks on demand and yields the ReplayData records one at a time.
+pub struct ReplayDecoder<R: Read> {
+    blocks: BlockReader<R>,
+    buffer: Vec<u8>,
+    position: usize,
+    offset: u64,
+    summary: ReplaySummary,
+    current_timestamp: u64,
+    game_start: Option<u64>,
+    selections: SelectionTracker,
+    raw: Option<RawReplay>,
+    finished: bool
+}
+
+impl<R: Read> ReplayDecoder<R> {
+    pub fn new(reader: R) -> Result<ReplayDecoder<R>, ReplayError> {
+        return ReplayDecoder::with_options(reader, &ParseOptions::default());
+    }
+
+    /// Reads the header and the records in front of the ReplayData (4.1 - 4.10).
+    pub fn with_options(mut reader: R, options: &ParseOptions) -> Result<ReplayDecoder<R>, ReplayError> {
+        let (header, raw_header) = header::read_header(&mut reader, options)?;
+        let mut decoder = ReplayDecoder {
+            blocks: BlockReader::new(reader, &header, options),
+            buffer: vec![],
+            position: 0,
+            offset: 0,
+            summary: ReplaySummary {
+                version: header.header_version as u8,
+                header,
+                record_player_id: 0,
+                record_player_is_host: false,
+                game_name: String::new(),
+                map_name: String::new(),
+                game_creator_battle_tag: String::new(),
+                game_settings: Default::default(),
+                game_start_info: Default::default(),
+                slots: vec![],
+                players: HashMap::new(),
+                reforged_records: vec![]
+            },
+            current_timestamp: 0,
+            game_start: None,
+            selections: SelectionTracker::new(),
+            raw: None,
+            finished: false
+        };
+
+        let game_info = decoder.read_with(read_game_info)?;
+        if options.keep_raw_records {
+            decoder.raw = Some(RawReplay {
+                header: raw_header,
+                game_info: decoder.buffer[..decoder.position].to_vec(),
+                ..Default::default()
+            });
+        }
+        decoder.summary.record_player_id = game_info.record_player_id;
+        decoder.summary.record_player_is_host = game_info.player_is_host;
+        decoder.summary.game_name = game_info.game_name;
+        decoder.summary.map_name = game_info.map_name;
+        decoder.summary.game_creator_battle_tag = game_info.game_creator_name;
+        decoder.summary.game_settings = game_info.game_settings;
+        decoder.summary.game_start_info = game_info.game_start_info;
+        decoder.summary.slots = game_info.slots;
+        decoder.summary.players = game_info.players;
+        decoder.summary.reforged_records = game_info.reforged_records;
+        return Ok(decoder);
+    }
+
+    pub fn summary(&self) -> &ReplaySummary {
+        return &self.summary;
+    }
+
+    // Changes are only seen by consumers of the summary, e.g. the writer
+    pub(crate) fn summary_mut(&mut self) -> &mut ReplaySummary {
+        return &mut self.summary;
+    }
+
+    pub fn into_summary(self) -> ReplaySummary {
+        return self.summary;
+    }
+
+    /// Timestamp at which the first TimeSlot with actions starts, once it was read.
+    /// Timestamps count from the first TimeSlot, so its start would always be 0.
+    pub fn game_start(&self) -> Option<u64> {
+        return self.game_start;
+    }
+
+    /// Selection state of all players after the records read so far.
+    pub fn selections(&self) -> &SelectionTracker {
+        return &self.selections;
+    }
+
+    /// Original bytes of everything read so far, if [`ParseOptions::keep_raw_records`] is set.
+    pub fn raw(&self) -> Option<&RawReplay> {
+        return self.raw.as_ref();
+    }
+
+    pub(crate) fn take_raw(&mut self) -> Option<RawReplay> {
+        return self.raw.take();
+    }
+
+    /// Game time in milliseconds of the last TimeSlot read so far.
+    pub fn timestamp(&self) -> u64 {
+        return self.current_timestamp;
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<ReplayRecord>, ReplayError> {
+        while !self.finished {
+            if self.position >= self.buffer.len() && self.blocks.is_finished() {
+                // The data ends right after a complete record
+                self.finished = true;
+                break;
+            }
+            let current_timestamp = self.current_timestamp;
+            let record_offset = self.offset + self.position as u64;
+            let decoded = self.read_with(|cursor| read_record(cursor, current_timestamp));
+            match decoded {
+                Ok(Decoded::Record(mut record)) => {
+                    if let ReplayRecord::Chat(message) = &mut record {
+                        if let ChatChannel::Private(slot) = message.channel {
+                            message.recipient_player_id = self.summary.slots.get(slot as usize).map(|s| s.player_id);
+                        }
+                    }
+                    if let ReplayRecord::TimeSlot(time_slot) = &mut record {
+                        if self.game_start.is_none() && time_slot.command_blocks.iter().any(|b| !b.actions.is_empty()) {
+                            self.game_start = Some(self.current_timestamp);
+                        }
+                        self.current_timestamp = time_slot.timestamp;
+                        for action in time_slot.command_blocks.iter_mut().flat_map(|b| b.actions.iter_mut()) {
+                            self.selections.apply(action);
+                        }
+                    }
+                    self.keep_raw_record(record_offset);
+                    return Ok(Some(record));
+                },
+                Ok(Decoded::End) => {
+                    self.finished = true;
+                    self.keep_trailing_data(record_offset)?;
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Err(e);
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    // Keeps the bytes of the record just read
+    fn keep_raw_record(&mut self, offset: u64) {
+        let Some(raw) = self.raw.as_mut() else {
+            return;
+        };
+        let data = self.buffer[(offset - self.offset) as usize..self.position].to_vec();
+        raw.records.push(RawRecord {
+            id: data[0],
+            offset,
+            timestamp: self.current_timestamp,
+            data
+        });
+    }
+
+    // Keeps everything from the end of the ReplayData on, inflating the remaining blocks
+    fn keep_trailing_data(&mut self, offset: u64) -> Result<(), ReplayError> {
+        if self.raw.is_none() {
+            return Ok(());
+        }
+        let mut data = self.buffer[(offset - self.offset) as usize..].to_vec();
+        while let Some(mut block) = self.blocks.next_block()? {
+            data.append(&mut block);
+        }
+        if let Some(raw) = self.raw.as_mut() {
+            raw.trailing_data = data;
+        }
+        return Ok(());
+    }
+
+    // Runs the parser on the buffered data, inflating further blocks until it no longer runs out of data
+    fn read_with<V, F>(&mut self, parse: F) -> Result<V, ReplayError> where F: Fn(&mut Cursor<&[u8]>) -> Result<V, ReplayError> {
+        loop {
+            let mut cursor = Cursor::new(&self.buffer[self.position..]);
+            match parse(&mut cursor) {
+                Ok(value) => {
+                    self.position += cursor.position() as usize;
+                    return Ok(value);
+                },
+                Err(ReplayError::UnexpectedEof { offset }) => {
+                    let base = self.offset + self.position as u64;
+                    match self.blocks.next_block()? {
+                        Some(mut block) => {
+                            self.buffer.drain(..self.position);
+                            self.offset += self.position as u64;
+                            self.position = 0;
+                            self.buffer.append(&mut block);
+                        },
+                        None => return Err(ReplayError::UnexpectedEof { offset: base + offset })
+                    }
+                },
+                Err(e) => return Err(e.with_base_offset(self.offset + self.position as u64))
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReplayDecoder<R> {
+    type Item = Result<ReplayRecord, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.next_record().transpose();
+    }
+}
+
+// 4.1 - 4.10: everything in front of the ReplayData
+fn read_game_info(cursor: &mut Cursor<&[u8]>) -> Result<GameInfo, ReplayError> {
+    // 4.0 [Decompressed data] starts with an unknown dword (0x00000110)
+    cursor_skip_bytes(cursor, 4)?;
+
+    // 4.1 [PlayerRecord]
+    let player_is_host = cursor_read_byte(cursor)? == 0x00;
+    let player_id = cursor_read_byte(cursor)?;
+    let player_name = cursor_read_nullterminated_string(cursor)?;
+    info!("Player name: {:?}", player_name);
+
+    let additional_data_size_byte = cursor_read_byte(cursor)?;
+    let mut additional_data = vec![0u8; additional_data_size_byte as usize];
+    cursor_read_exact(cursor, &mut additional_data)?;
+
+    // 4.2 [GameName]
+    let game_name = cursor_read_nullterminated_string(cursor)?;
+    info!("Game name: {:?}", game_name);
+
+    // There seems to be an additional NUL byte
+    cursor_skip_bytes(cursor, 1)?;
+
+    // 4.3 [Encoded String]
+    let encoded_gamesettings_offset = cursor.position();
+    let mut encoded_gamesettings_buf: Vec<u8> = vec![];
+    cursor.read_until(0x00, &mut encoded_gamesettings_buf)
+        .map_err(|_| ReplayError::UnexpectedEof { offset: encoded_gamesettings_offset })?;
+    if encoded_gamesettings_buf.last() != Some(&0x00) {
+        return Err(ReplayError::UnexpectedEof { offset: encoded_gamesettings_offset });
+    }
+
+    let game_settings_buf = decode_gamesettings(&encoded_gamesettings_buf);
+    info!("Decoded gamesettings: {:?}", game_settings_buf);
+    if game_settings_buf.len() < 13 {
+        return Err(ReplayError::MalformedGameSettings { length: game_settings_buf.len() });
+    }
+
+    // 4.4 [GameSettings]
+    let game_speed = get_bits_value(game_settings_buf[0], [0, 1].as_ref());
+    let vis_hide_terrain = get_bits_value(game_settings_buf[1], [0].as_ref()) == 1;
+    let vis_map_explored = get_bits_value(game_settings_buf[1], [1].as_ref()) == 1;
+    let vis_always_visible = get_bits_value(game_settings_buf[1], [2].as_ref()) == 1;
+    let vis_default = get_bits_value(game_settings_buf[1], [3].as_ref()) == 1;
+    let obs_mode = get_bits_value(game_settings_buf[1], [4, 5].as_ref());
+    let teams_together = get_bits_value(game_settings_buf[1], [6].as_ref()) == 1;
+
+    let fixed_teams = get_bits_value(game_settings_buf[2], [1,2].as_ref());
+    let shared_unit_control = get_bits_value(game_settings_buf[3], [0].as_ref()) == 1;
+    let random_hero = get_bits_value(game_settings_buf[3], [1].as_ref()) == 1;
+    let random_races = get_bits_value(game_settings_buf[3], [2].as_ref()) == 1;
+    let obs_referees = get_bits_value(game_settings_buf[3], [6].as_ref()) == 1;
+
+    // 4.5 [Map&CreatorName]
+    let mut subcursor = Cursor::new(game_settings_buf[13..].as_ref());
+    let map_name = cursor_read_nullterminated_string(&mut subcursor)?;
+    let game_creator_name = cursor_read_nullterminated_string(&mut subcursor)?;
+    let extra_data = game_settings_buf[13 + subcursor.position() as usize..].to_vec();
+
+    // 4.6 [PlayerCount]
+    let num_players_slots = cursor_read_dword(cursor)?;
+
+    // 4.7 [GameType]
+    let game_type = cursor_read_byte(cursor)?;
+    let mut game_type_raw = [0u8; 3];
+    cursor_read_exact(cursor, &mut game_type_raw)?;
+    let is_private_custom_game = game_type_raw[0] == 0x08;
+    info!("Player slots: {:?}, game type: {:?}, private custom game: {:?}", num_players_slots, game_type, is_private_custom_game);
+
+    // 4.8 [LanguageID?]
+    let language_id = cursor_read_dword(cursor)?;
+
+    // 4.9 [PlayerList]
+    let mut player_list: HashMap<u8, ReplayPlayer> = HashMap::new();
+    player_list.insert(player_id,
+                       ReplayPlayer {
+                           battle_tag: player_name.clone(),
+                           leave_reason: LeaveReason::UNKNOWN(0),
+                           result_byte: 0,
+                           left_at: 0,
+                           reforged: None,
+                           additional_data,
+                           record_unknown: 0
+                       }
+    );
+    let mut next_record_id = cursor_read_byte(cursor)?;
+    while next_record_id == 0x16 {
+        let cur_player_id = cursor_read_byte(cursor)?;
+        let cur_player_name = cursor_read_nullterminated_string(cursor)?;
+        let additional_data_size_byte = cursor_read_byte(cursor)?;
+        let mut additional_data = vec![0u8; additional_data_size_byte as usize];
+        cursor_read_exact(cursor, &mut additional_data)?;
+        // Every record is followed by an unknown dword
+        let record_unknown = cursor_read_dword(cursor)?;
+        player_list.insert(cur_player_id, ReplayPlayer {
+            battle_tag: cur_player_name,
+            leave_reason: LeaveReason::UNKNOWN(0),
+            result_byte: 0,
+            left_at: 0,
+            reforged: None,
+            additional_data,
+            record_unknown
+        });
+        next_record_id = cursor_read_byte(cursor)?;
+    }
+    info!("Loaded player list: {:?}", player_list);
+
+    // Reforged player metadata
+    let mut reforged_records: Vec<ReforgedRecord> = vec![];
+    while next_record_id == 0x39 {
+        let cur_record_subtype = cursor_read_byte(cursor)?;
+        let cur_record_data_length = cursor_read_dword(cursor)?;
+        info!("Reforged metadata record: subtype = {:?}, length = {:?}", cur_record_subtype, cur_record_data_length);
+
+        // The length is not trusted for an allocation, the record may continue in the next data block
+        let data_start = cursor.position();
+        let data_end = data_start + cur_record_data_length as u64;
+        if data_end > cursor.get_ref().len() as u64 {
+            return Err(ReplayError::UnexpectedEof { offset: data_start });
+        }
+        let data = cursor.get_ref()[data_start as usize..data_end as usize].to_vec();
+        cursor.set_position(data_end);
+        match decode_protobuf(&data) {
+            Some(fields) => reforged_records.push(ReforgedRecord { subtype: cur_record_subtype, fields, raw_data: None }),
+            None => {
+                warn!("Reforged metadata record is no valid protobuf message: {:?}", data);
+                reforged_records.push(ReforgedRecord { subtype: cur_record_subtype, fields: vec![], raw_data: Some(data) });
+            }
+        }
+
+        next_record_id = cursor_read_byte(cursor)?;
+    }
+
+    // Player profiles first, so the other records find their player
+    reforged_records.sort_by_key(|r| r.subtype != PLAYER_PROFILE_SUBTYPE);
+    let mut unassigned_reforged_records: Vec<ReforgedRecord> = vec![];
+    for record in reforged_records {
+        let player = record.player_id()
+            .and_then(|id| u8::try_from(id).ok())
+            .and_then(|id| player_list.get_mut(&id));
+        let Some(player) = player else {
+            info!("Reforged metadata record for unknown player: {:?}", record);
+            unassigned_reforged_records.push(record);
+            continue;
+        };
+        if record.subtype == PLAYER_PROFILE_SUBTYPE && player.reforged.is_none() {
+            player.reforged = Some(ReforgedPlayerMetadata::from_profile(record));
+        }
+        else {
+            let player_id = record.player_id().unwrap_or_default();
+            player.reforged.get_or_insert_with(|| ReforgedPlayerMetadata { player_id, ..Default::default() }).other_records.push(record);
+        }
+    }
+
+    // 4.10 [GameStartRecord]
+    if next_record_id != 0x19 {
+        let offset = cursor.position() - 1;
+        warn!("GameStartRecord did not follow PlayerList: next record id = {:?}.", next_record_id);
+        return Err(ReplayError::UnexpectedRecord { id: next_record_id, offset });
+    }
+
+    let data_length = cursor_read_word(cursor)?;
+    let count_slotrecords = cursor_read_byte(cursor)?;
+    info!("GameStartRecord: length = {:?}, slot records = {:?}", data_length, count_slotrecords);
+    let mut i = 0u8;
+
+    let mut slots: Vec<Slot> = Vec::with_capacity(count_slotrecords as usize);
+
+    while i < count_slotrecords {
+        let slot_record_offset = cursor.position() as usize;
+        let cur_slot_player_id = cursor_read_byte(cursor)?;
+        let cur_slot_map_download_percent = cursor_read_byte(cursor)?;
+        let status_byte = cursor_read_byte(cursor)?;
+        let cur_slot_status = SlotStatus::from_u8(status_byte)
+            .or(Option::from(SlotStatus::UNKNOWN))
+            .unwrap();
+        let cur_slot_is_computer_player = cursor_read_byte(cursor)? == 1;
+        let cur_slot_team_index = cursor_read_byte(cursor)?;
+        let color_byte = cursor_read_byte(cursor)?;
+        let cur_slot_color =
+            color_byte.checked_add(1).and_then(SlotColor::from_u8)
+                .or(Option::from(SlotColor::UNKNOWN))
+                .unwrap();
+        let race_byte = cursor_read_byte(cursor)?;
+        let cur_slot_player_race =
+            SlotRace::from_u8(race_byte)
+                .or(Option::from(SlotRace::UNKNOWN))
+                .unwrap();
+        let cur_slot_player_computer_ai_strenth =
+            ComputerAIStrength::from_u8(cursor_read_byte(cursor)?)
+                .or(Option::from(ComputerAIStrength::UNKNOWN))
+                .unwrap();
+        let cur_slot_handicap_percent = cursor_read_byte(cursor)?;
+
+        info!("Player slot record read: pid = {:?} status = {:?} is_comp = {:?} team = {:?} color = {:?} ({:?}) race = {:?} ({:?})",
+            cur_slot_player_id, cur_slot_status, cur_slot_is_computer_player, cur_slot_team_index, cur_slot_color, color_byte, cur_slot_player_race, race_byte);
+
+        slots.push(Slot {
+            player_id: cur_slot_player_id,
+            map_download_percent: cur_slot_map_download_percent,
+            status: cur_slot_status,
+            is_computer: cur_slot_is_computer_player,
+            team_index: cur_slot_team_index,
+            color: cur_slot_color,
+            race: cur_slot_player_race,
+            race_flags: race_byte,
+            ai_strength: cur_slot_player_computer_ai_strenth,
+            handicap_percent: cur_slot_handicap_percent,
+            raw: cursor.get_ref()[slot_record_offset..slot_record_offset + 9].try_into().unwrap()
+        });
+
+        i+=1;
+    }
+
+    let random_seed = cursor_read_dword(cursor)?;
+    info!("Random seed: {:?}", random_seed);
+    let selection_mode = cursor_read_byte(cursor)?;
+    info!("Selection mode: {:?}", selection_mode);
+    let start_spot_count = cursor_read_byte(cursor)?;
+    info!("Start spots count: {:?}", start_spot_count);
+
+    return Ok(GameInfo {
+        player_is_host,
+        record_player_id: player_id,
+        game_name,
+        map_name,
+        game_creator_name,
+        game_start_info: GameStartInfo {
+            num_players_slots,
+            game_type: GameType::from_u8(game_type),
+            is_private_custom_game,
+            language_id,
+            random_seed,
+            selection_mode: SlotSelectionMode::from_u8(selection_mode),
+            start_spot_count,
+            game_type_raw
+        },
+        game_settings: GameSettings {
+            fixed_teams,
+            shared_unit_control,
+            random_hero,
+            random_races,
+            obs_referees,
+            vis_default,
+            vis_hide_terrain,
+            vis_always_visible,
+            vis_map_explored,
+            teams_together,
+            obs_mode,
+            game_speed,
+            map_checksum: parse_dword(&game_settings_buf[9..13]),
+            extra_data,
+            raw: game_settings_buf[..9].try_into().unwrap()
+        },
+        slots,
+        players: player_list,
+        reforged_records: unassigned_reforged_records
+    });
+}
+
+// 5.0 [ReplayData]
+fn read_record<T>(cursor: &mut Cursor<T>, current_timestamp: u64) -> Result<Decoded, ReplayError> where T: AsRef<[u8]> {
+    let next_record_id = cursor_read_byte(cursor)?;
+    // info!("Position {:?}, record {:?}", cursor.position() - 1, next_record_id);
+    match next_record_id {
+        0x17 => {
+            let leave_reason_byte = cursor_read_dword(cursor)?;
+            let cur_leave_reason = LeaveReason::from_u32(leave_reason_byte);
+            let cur_player_id = cursor_read_byte(cursor)?;
+            let cur_result = cursor_read_dword(cursor)?;
+            let cur_unknown = cursor_read_dword(cursor)?;
+
+            info!("{:?} {:?}", cur_leave_reason, cur_result);
+            return Ok(Decoded::Record(ReplayRecord::LeaveGame(PlayerLeave {
+                player_id: cur_player_id,
+                reason: cur_leave_reason,
+                result: cur_result,
+                unknown: cur_unknown,
+                timestamp: current_timestamp
+            })));
+        },
+        0x1A..=0x1C => {
+            return Ok(Decoded::Record(ReplayRecord::Startup(StartupRecord {
+                record_type: StartupRecordType::from_u8(next_record_id).or(Option::from(StartupRecordType::UNKNOWN)).unwrap(),
+                value: cursor_read_dword(cursor)?,
+                timestamp: current_timestamp
+            })));
+        },
+        0x1E | 0x1F => {
+            return Ok(Decoded::Record(ReplayRecord::TimeSlot(read_time_slot(cursor, next_record_id, current_timestamp)?)));
+        },
+        0x20 => {
+            let cur_player_id = cursor_read_byte(cursor)?;
+            cursor_skip_bytes(cursor, 2)?;
+            let cur_flag = cursor_read_byte(cursor)?;
+            // Startup screen messages have no chat mode
+            let channel = match cur_flag {
+                0x10 => ChatChannel::Lobby,
+                _ => match cursor_read_dword(cursor)? {
+                    0x00 => ChatChannel::All,
+                    0x01 => ChatChannel::Allies,
+                    0x02 => ChatChannel::Observers,
+                    mode @ 0x03..=0xFF => ChatChannel::Private((mode - 3) as u8),
+                    mode => ChatChannel::Unknown(mode)
+                }
+            };
+            let cur_message = cursor_read_nullterminated_string(cursor)?;
+            return Ok(Decoded::Record(ReplayRecord::Chat(ChatMessage {
+                sender_player_id: cur_player_id,
+                channel,
+                recipient_player_id: None,
+                flag: Option::from(cur_flag),
+                message: cur_message,
+                timestamp: current_timestamp
+            })));
+        },
+        0x22 | 0x23 | 0x2F => {
+            let length = match next_record_id {
+                0x22 => 5,
+                0x23 => 10,
+                _ => 8
+            };
+            let mut data = vec![0u8; length];
+            cursor_read_exact(cursor, &mut data)?;
+            return Ok(Decoded::Record(ReplayRecord::Other(OtherRecord {
+                id: next_record_id,
+                data,
+                timestamp: current_timestamp
+            })));
+        },
+        0x00 => {
+            info!("Exiting at null. Position: {:?}", cursor.position());
+            return Ok(Decoded::End);
+        }
+        _ => {
+            return Err(ReplayError::UnexpectedRecord { id: next_record_id, offset: cursor.position() - 1 });
+        }
+    }
+}
+
+// 0x1E / 0x1F [TimeSlot]
+fn read_time_slot<T>(cursor: &mut Cursor<T>, record_id: u8, timestamp: u64) -> Result<TimeSlot, ReplayError> where T: AsRef<[u8]> {
+    let record_offset = cursor.position() - 1;
+    let invalid_length = move || ReplayError::InvalidRecordLength { id: record_id, offset: record_offset };
+    let mut len_following = cursor_read_word(cursor)?;
+    let increment = cursor_read_word(cursor)?;
+    // info!("Time increment: {:?}", increment);
+    let current_timestamp = timestamp + increment as u64;
+    len_following = len_following.checked_sub(2).ok_or_else(invalid_length)?;
+    let total_len_following = len_following;
+    let cursor_position_before_data_read = cursor.position();
+    let mut command_blocks: Vec<CommandBlock> = vec![];
+
+    if len_following > 0 {
+        loop {
+            let cur_action_player_id = cursor_read_byte(cursor)?;
+            let cur_action_blocks_length = cursor_read_word(cursor)?;
+            len_following = len_following.checked_sub(3).ok_or_else(invalid_length)?;
+
+            let position_before_read = cursor.position();
+            let mut actions: Vec<Action> = vec![];
+            let mut cur_read_bytes = 0;
+            while cur_read_bytes < cur_action_blocks_length {
+                let cur_position_before_read = cursor.position();
+
+                let cur_action_id = cursor_read_byte(cursor)?;
+
+                let kind = match cur_action_id {
+                    0x01 => ActionKind::Pause,
+                    0x02 => ActionKind::Resume,
+                    0x03 => ActionKind::SetGameSpeed {
+                        speed: cursor_read_byte(cursor)?
+                    },
+                    0x04 => ActionKind::IncreaseGameSpeed,
+                    0x05 => ActionKind::DecreaseGameSpeed,
+                    0x06 => ActionKind::SaveGame {
+                        name: cursor_read_nullterminated_string(cursor)?
+                    },
+                    0x07 => ActionKind::SaveGameDone {
+                        unknown: cursor_read_dword(cursor)?
+                    },
+                    0x10 => {
+                        let flags = cursor_read_word(cursor)?;
+                        let item_id = cursor_read_ability_itemid(cursor)?;
+                        ActionKind::AbilityBasic {
+                            flags,
+                            item_id,
+                            unknown_a: cursor_read_dword(cursor)?,
+                            unknown_b: cursor_read_dword(cursor)?
+                        }
+                    },
+                    0x11 => {
+                        let flags = cursor_read_word(cursor)?;
+                        let item_id = cursor_read_ability_itemid(cursor)?;
+                        ActionKind::AbilityWithTargetLocation {
+                            flags,
+                            item_id,
+                            unknown_a: cursor_read_dword(cursor)?,
+                            unknown_b: cursor_read_dword(cursor)?,
+                            location: cursor_read_location(cursor)?
+                        }
+                    },
+                    0x12 => {
+                        let flags = cursor_read_word(cursor)?;
+                        let item_id = cursor_read_ability_itemid(cursor)?;
+                        ActionKind::AbilityWithTargetObject {
+                            flags,
+                            item_id,
+                            unknown_a: cursor_read_dword(cursor)?,
+                            unknown_b: cursor_read_dword(cursor)?,
+                            location: cursor_read_location(cursor)?,
+                            target: cursor_read_object_ids(cursor)?
+                        }
+                    },
+                    0x13 => {
+                        let flags = cursor_read_word(cursor)?;
+                        let item_id = cursor_read_ability_itemid(cursor)?;
+                        ActionKind::ItemTransfer {
+                            flags,
+                            item_id,
+                            unknown_a: cursor_read_dword(cursor)?,
+                            unknown_b: cursor_read_dword(cursor)?,
+                            location: cursor_read_location(cursor)?,
+                            target: cursor_read_object_ids(cursor)?,
+                            item: cursor_read_object_ids(cursor)?
+                        }
+                    },
+                    0x14 => {
+                        let flags = cursor_read_word(cursor)?;
+                        let item_id = cursor_read_ability_itemid(cursor)?;
+                        let unknown_a = cursor_read_dword(cursor)?;
+                        let unknown_b = cursor_read_dword(cursor)?;
+                        let location = cursor_read_location(cursor)?;
+                        let item_id_b = cursor_read_ability_itemid(cursor)?;
+                        let mut unknown = [0u8; 9];
+                        cursor_read_exact(cursor, &mut unknown)?;
+                        ActionKind::AbilityWithTwoTargetLocations {
+                            flags,
+                            item_id,
+                            unknown_a,
+                            unknown_b,
+                            location,
+                            item_id_b,
+                            unknown,
+                            location_b: cursor_read_location(cursor)?
+                        }
+                    },
+                    0x16 => {
+                        let select_mode_byte = cursor_read_byte(cursor)?;
+                        let num_units = cursor_read_word(cursor)?;
+                        let mut objects: Vec<ObjectIDs> = vec![];
+                        for _ in 0..num_units {
+                            objects.push(cursor_read_object_ids(cursor)?);
+                        }
+                        ActionKind::ChangeSelection {
+                            mode: SelectionMode::from_u8(select_mode_byte).or(Option::from(SelectionMode::UNKNOWN)).unwrap(),
+                            objects
+                        }
+                    },
+                    0x17 => {
+                        let group = cursor_read_byte(cursor)?;
+                        let items_count = cursor_read_word(cursor)?;
+                        let mut objects: Vec<ObjectIDs> = vec![];
+                        for _ in 0..items_count {
+                            objects.push(cursor_read_object_ids(cursor)?);
+                        }
+                        ActionKind::GroupAssign {
+                            group,
+                            objects
+                        }
+                    },
+                    0x18 => ActionKind::GroupSelect {
+                        group: cursor_read_byte(cursor)?,
+                        unknown: cursor_read_byte(cursor)?
+                    },
+                    0x19 => ActionKind::SelectSubgroup {
+                        item_id: cursor_read_ability_itemid(cursor)?,
+                        object: cursor_read_object_ids(cursor)?
+                    },
+                    0x1A => ActionKind::PreSubselection,
+                    0x1B => ActionKind::Unknown1B {
+                        unknown: cursor_read_byte(cursor)?,
+                        object: cursor_read_object_ids(cursor)?
+                    },
+                    0x1C => ActionKind::SelectGroundItem {
+                        flags: cursor_read_byte(cursor)?,
+                        object: cursor_read_object_ids(cursor)?
+                    },
+                    0x1D => ActionKind::CancelHeroRevival {
+                        hero: cursor_read_object_ids(cursor)?
+                    },
+                    0x1E => ActionKind::RemoveFromQueue {
+                        slot: cursor_read_byte(cursor)?,
+                        item_id: cursor_read_ability_itemid(cursor)?
+                    },
+                    0x21 => ActionKind::Unknown21 {
+                        unknown_a: cursor_read_dword(cursor)?,
+                        unknown_b: cursor_read_dword(cursor)?
+                    },
+
+                    0x27 | 0x28 | 0x2D => ActionKind::CheatResources {
+                        cheat: ActionType::from_u8(cur_action_id).unwrap(),
+                        unknown: cursor_read_byte(cursor)?,
+                        amount: cursor_read_dword(cursor)?
+                    },
+                    0x2E => ActionKind::CheatDaylightSavings {
+                        time_of_day: cursor_read_dword_float(cursor)?
+                    },
+                    0x20 | 0x22..=0x26 | 0x29..=0x2C | 0x2F..=0x32 => ActionKind::Cheat {
+                        cheat: ActionType::from_u8(cur_action_id).unwrap()
+                    },
+
+                    0x50 => ActionKind::ChangeAllyOptions {
+                        slot: cursor_read_byte(cursor)?,
+                        flags: cursor_read_dword(cursor)?
+                    },
+                    0x51 => ActionKind::TransferResources {
+                        slot: cursor_read_byte(cursor)?,
+                        gold: cursor_read_dword(cursor)?,
+                        lumber: cursor_read_dword(cursor)?
+                    },
+
+                    0x60 => {
+                        let unknown_a = cursor_read_dword(cursor)?;
+                        let unknown_b = cursor_read_dword(cursor)?;
+                        let command = cursor_read_nullterminated_string(cursor)?;
+                        info!("Chat command (time {}) (player {}): {} {:?}", current_timestamp, cur_action_player_id, command, (unknown_a, unknown_b));
+                        ActionKind::TriggerChat {
+                            unknown_a,
+                            unknown_b,
+                            message: command
+                        }
+                    },
+                    0x61 => ActionKind::EscPressed,
+                    0x62 => ActionKind::ScenarioTrigger {
+                        unknown_a: cursor_read_dword(cursor)?,
+                        unknown_b: cursor_read_dword(cursor)?,
+                        unknown_c: cursor_read_dword(cursor)?
+                    },
+                    0x66 => ActionKind::ChooseHeroSkillSubmenu,
+                    0x67 => ActionKind::ChooseBuildingSubmenu,
+                    0x68 => ActionKind::MinimapSignal {
+                        location: cursor_read_location(cursor)?,
+                        unknown: cursor_read_dword(cursor)?
+                    },
+                    0x69 => ActionKind::ContinueGameB {
+                        unknown: cursor_read_dwords(cursor)?
+                    },
+                    0x6A => ActionKind::ContinueGameA {
+                        unknown: cursor_read_dwords(cursor)?
+                    },
+                    // SyncStoredInteger etc., used by W3MMD
+                    0x6B => ActionKind::SyncStoreInteger {
+                        filename: cursor_read_nullterminated_string(cursor)?,
+                        mission_key: cursor_read_nullterminated_string(cursor)?,
+                        key: cursor_read_nullterminated_string(cursor)?,
+                        value: cursor_read_dword(cursor)?
+                    },
+                    0x6C => ActionKind::SyncStoreUnknown6C {
+                        filename: cursor_read_nullterminated_string(cursor)?,
+                        mission_key: cursor_read_nullterminated_string(cursor)?,
+                        key: cursor_read_nullterminated_string(cursor)?
+                    },
+                    0x6D => ActionKind::SyncStoreUnknown6D {
+                        filename: cursor_read_nullterminated_string(cursor)?,
+                        mission_key: cursor_read_nullterminated_string(cursor)?,
+                        key: cursor_read_nullterminated_string(cursor)?,
+                        value: cursor_read_dword(cursor)?
+                    },
+                    0x75 => ActionKind::ArrowKey {
+                        key: cursor_read_byte(cursor)?
+                    },
+
+                    // Unknown
+                    0x7a => ActionKind::Unknown7A {
+                        unknown: cursor_read_dwords(cursor)?
+                    },
+                    0x7b => ActionKind::Unknown7B {
+                        unknown: cursor_read_dwords(cursor)?
+                    },
+
+                    _ => {
+                        let cur_pos = cursor.position();
+                        let left_bytes = (cur_action_blocks_length as u64 + position_before_read)
+                            .checked_sub(cur_pos)
+                            .ok_or_else(invalid_length)?;
+                        warn!("({}) Unknown action id: {:#04x}. Read bytes so far: {:?}. Total expected: {:?}", cur_read_bytes, cur_action_id, cur_pos - position_before_read, cur_action_blocks_length);
+                        let mut buf = vec![0u8; left_bytes as usize];
+                        cursor_read_exact(cursor, &mut buf)?;
+                        info!("Following bytes: {:?}", buf);
+                        break;
+                    }
+                };
+
+                actions.push(Action {
+                    player_id: cur_action_player_id,
+                    timestamp: current_timestamp,
+                    kind,
+                    units: None
+                });
+
+                let cur_bytes = (cursor.position() - cur_position_before_read) as u16;
+                cur_read_bytes += cur_bytes;
+            }
+
+            command_blocks.push(CommandBlock {
+                player_id: cur_action_player_id,
+                actions
+            });
+
+            len_following = len_following
+                .checked_sub((cursor.position() - position_before_read) as u16)
+                .ok_or_else(invalid_length)?;
+
+            if len_following < 1 { break }
+        }
+    }
+
+    if cursor.position() - cursor_position_before_data_read != total_len_following as u64 {
+        warn!("Mismatch: {:?}/{:?}", cursor.position() - cursor_position_before_data_read, total_len_following);
+    }
+
+    return Ok(TimeSlot {
+        timestamp: current_timestamp,
+        increment,
+        command_blocks
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{chat, summary, time_slot, write};
+
+    fn bytes() -> Vec<u8> {
+        return write(&summary(&["Alice#123", "Bob#456"]), &[
+            chat(1, ChatChannel::Lobby, "gl"),
+            time_slot(100, 1, vec![ActionKind::ChangeAllyOptions { slot: 1, flags: 0x1F }]),
+            time_slot(100, 2, vec![ActionKind::EscPressed])
+        ]);
+    }
+
+    #[test]
+    fn truncated_header() {
+        let bytes = bytes();
+        assert!(matches!(Replay::try_from_bytes(&bytes[..30]), Err(ReplayError::TruncatedHeader { length: 30 })));
+        assert!(matches!(Replay::try_from_bytes(&bytes[..60]), Err(ReplayError::TruncatedHeader { length: 60 })));
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = bytes();
+        bytes[0] = b'w';
+        assert!(matches!(Replay::try_from_bytes(&bytes), Err(ReplayError::BadMagic)));
+    }
+
+    #[test]
+    fn inflated_length_mismatch() {
+        let mut bytes = bytes();
+        // Inflated length of the first block header, following the 68 byte header
+        bytes[68 + 4..68 + 8].copy_from_slice(&4000u32.to_le_bytes());
+        assert!(matches!(Replay::try_from_bytes(&bytes),
+            Err(ReplayError::InflatedLengthMismatch { block: 0, offset: 68, expected: 4000, actual: 4001 })));
+    }
+
+    #[test]
+    fn unknown_record_id() {
+        let options = ParseOptions { keep_raw_records: true, ..Default::default() };
+        let mut raw = Replay::try_from_bytes_with_options(&bytes(), &options).unwrap().raw.unwrap();
+        let offset = (raw.game_info.len() + raw.records.iter().map(|r| r.data.len()).sum::<usize>()) as u64;
+        raw.records.push(RawRecord { id: 0x99, offset, timestamp: 200, data: vec![0x99, 0x00, 0x00] });
+        let bytes = ReplayWriter::from_raw(&raw).unwrap().into_bytes().unwrap();
+        assert!(matches!(Replay::try_from_bytes(&bytes), Err(ReplayError::UnexpectedRecord { id: 0x99, offset: o }) if o == offset));
+    }
+
+    #[test]
+    fn truncation_at_every_offset_is_an_error() {
+        let bytes = bytes();
+        assert!(Replay::try_from_bytes(&bytes).is_ok());
+        for length in 0..bytes.len() {
+            assert!(Replay::try_from_bytes(&bytes[..length]).is_err(), "truncated to {} bytes", length);
+        }
+    }
+}
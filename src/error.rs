@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::{fmt, io};
+use flate2::DecompressError;
+
+/// Errors returned by [`crate::Replay::try_from_bytes`] and the functions writing replays.
+///
+/// Offsets of data blocks refer to the raw file, all other offsets refer to the decompressed data stream.
+#[derive(Debug)]
+pub enum ReplayError {
+    TruncatedHeader { length: usize },
+    BadMagic,
+    HeaderChecksumMismatch { expected: u32, actual: u32 },
+    TruncatedBlock { block: u32, offset: u64 },
+    BlockChecksumMismatch { block: u32, offset: u64, expected: u32, actual: u32 },
+    Decompression { block: u32, source: DecompressError },
+    InflatedLengthMismatch { block: u32, offset: u64, expected: u32, actual: usize },
+    UnexpectedEof { offset: u64 },
+    UnexpectedRecord { id: u8, offset: u64 },
+    InvalidRecordLength { id: u8, offset: u64 },
+    MalformedGameSettings { length: usize },
+    ValueTooLarge { field: &'static str, value: u64 },
+    InvalidClipRange { start: u64, end: u64 },
+    Write { source: io::Error }
+}
+
+impl ReplayError {
+    // Offsets are relative to the data handed to the parser - make them absolute
+    pub(crate) fn with_base_offset(self, base: u64) -> ReplayError {
+        match self {
+            ReplayError::UnexpectedEof { offset } =>
+                ReplayError::UnexpectedEof { offset: base + offset },
+            ReplayError::UnexpectedRecord { id, offset } =>
+                ReplayError::UnexpectedRecord { id, offset: base + offset },
+            ReplayError::InvalidRecordLength { id, offset } =>
+                ReplayError::InvalidRecordLength { id, offset: base + offset },
+            e => e
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::TruncatedHeader { length } =>
+                write!(f, "replay header is truncated ({} bytes)", length),
+            ReplayError::BadMagic =>
+                write!(f, "not a Warcraft III replay (bad magic string)"),
+            ReplayError::HeaderChecksumMismatch { expected, actual } =>
+                write!(f, "header checksum mismatch (expected {:#010x}, found {:#010x})", expected, actual),
+            ReplayError::TruncatedBlock { block, offset } =>
+                write!(f, "data block {} at offset {:#x} is truncated", block, offset),
+            ReplayError::BlockChecksumMismatch { block, offset, expected, actual } =>
+                write!(f, "checksum mismatch in data block {} at offset {:#x} (expected {:#010x}, found {:#010x})", block, offset, expected, actual),
+            ReplayError::Decompression { block, source } =>
+                write!(f, "failed to decompress data block {}: {}", block, source),
+            ReplayError::InflatedLengthMismatch { block, offset, expected, actual } =>
+                write!(f, "data block {} at offset {:#x} inflates to {} bytes instead of {}", block, offset, actual, expected),
+            ReplayError::UnexpectedEof { offset } =>
+                write!(f, "unexpected end of data at offset {:#x}", offset),
+            ReplayError::UnexpectedRecord { id, offset } =>
+                write!(f, "unexpected record id {:#04x} at offset {:#x}", id, offset),
+            ReplayError::InvalidRecordLength { id, offset } =>
+                write!(f, "invalid length in record {:#04x} at offset {:#x}", id, offset),
+            ReplayError::MalformedGameSettings { length } =>
+                write!(f, "encoded game settings are too short ({} bytes)", length),
+            ReplayError::ValueTooLarge { field, value } =>
+                write!(f, "{} {} is too large to write", field, value),
+            ReplayError::InvalidClipRange { start, end } =>
+                write!(f, "clip starts at {} ms, after its end at {} ms", start, end),
+            ReplayError::Write { source } =>
+                write!(f, "failed to write replay: {}", source),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Decompression { source, .. } => Some(source),
+            ReplayError::Write { source } => Some(source),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use serde::Serialize;
+use crate::{Action, ChatMessage, OtherRecord, PlayerLeave, ReplayDecoder, ReplayError, ReplayRecord, ReplaySummary, StartupRecord, TimeSlot};
+
+#[derive(Serialize, Debug)]
+pub enum ReplayEvent {
+    Startup(StartupRecord),
+    TimeSlot { timestamp: u64, increment: u16 },
+    Action(Action),
+    Chat(ChatMessage),
+    Leave(PlayerLeave),
+    Other(OtherRecord)
+}
+
+impl ReplayEvent {
+    pub fn timestamp(&self) -> u64 {
+        return match self {
+            ReplayEvent::Startup(startup_record) => startup_record.timestamp,
+            ReplayEvent::TimeSlot { timestamp, .. } => *timestamp,
+            ReplayEvent::Action(action) => action.timestamp,
+            ReplayEvent::Chat(message) => message.timestamp,
+            ReplayEvent::Leave(leave) => leave.timestamp,
+            ReplayEvent::Other(record) => record.timestamp
+        };
+    }
+}
+
+/// Callbacks for [`ReplayDecoder::visit`]. All methods default to doing nothing.
+#[allow(unused_variables)]
+pub trait ReplayVisitor {
+    fn on_startup(&mut self, startup_record: &StartupRecord) {}
+    /// Called once per TimeSlot, before `on_action` is called for each of its actions.
+    fn on_timeslot(&mut self, time_slot: &TimeSlot) {}
+    fn on_action(&mut self, action: &Action) {}
+    fn on_chat(&mut self, message: &ChatMessage) {}
+    fn on_leave(&mut self, leave: &PlayerLeave) {}
+    fn on_other(&mut self, record: &OtherRecord) {}
+}
+
+/// Iterator over the ReplayData with every TimeSlot flattened into its actions.
+pub struct ReplayEvents<R: Read> {
+    decoder: ReplayDecoder<R>,
+    pending: VecDeque<ReplayEvent>
+}
+
+impl<R: Read> ReplayEvents<R> {
+    pub fn summary(&self) -> &ReplaySummary {
+        return self.decoder.summary();
+    }
+}
+
+impl<R: Read> Iterator for ReplayEvents<R> {
+    type Item = Result<ReplayEvent, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+
+        let record = match self.decoder.next_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e))
+        };
+        let event = match record {
+            ReplayRecord::Startup(startup_record) => ReplayEvent::Startup(startup_record),
+            ReplayRecord::LeaveGame(leave) => ReplayEvent::Leave(leave),
+            ReplayRecord::Chat(message) => ReplayEvent::Chat(message),
+            ReplayRecord::Other(record) => ReplayEvent::Other(record),
+            ReplayRecord::TimeSlot(time_slot) => {
+                for command_block in time_slot.command_blocks {
+                    self.pending.extend(command_block.actions.into_iter().map(ReplayEvent::Action));
+                }
+                ReplayEvent::TimeSlot { timestamp: time_slot.timestamp, increment: time_slot.increment }
+            }
+        };
+        return Some(Ok(event));
+    }
+}
+
+impl<R: Read> ReplayDecoder<R> {
+    pub fn events(self) -> ReplayEvents<R> {
+        return ReplayEvents {
+            decoder: self,
+            pending: VecDeque::new()
+        };
+    }
+
+    /// Decodes all remaining records and hands them to the visitor.
+    pub fn visit<V: ReplayVisitor>(&mut self, visitor: &mut V) -> Result<(), ReplayError> {
+        while let Some(record) = self.next_record()? {
+            match &record {
+                ReplayRecord::Startup(startup_record) => visitor.on_startup(startup_record),
+                ReplayRecord::LeaveGame(leave) => visitor.on_leave(leave),
+                ReplayRecord::Chat(message) => visitor.on_chat(message),
+                ReplayRecord::Other(record) => visitor.on_other(record),
+                ReplayRecord::TimeSlot(time_slot) => {
+                    visitor.on_timeslot(time_slot);
+                    for action in time_slot.command_blocks.iter().flat_map(|b| b.actions.iter()) {
+                        visitor.on_action(action);
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+}
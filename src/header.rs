@@ -0,0 +1,159 @@
+use std::io::Read;
+use flate2::Crc;
+use log::info;
+use serde::Serialize;
+use crate::{parse_dword, parse_word, ParseOptions, ReplayError};
+
+pub(crate) const REPLAY_MAGIC: &[u8] = b"Warcraft III recorded game\x1A";
+
+const MULTIPLAYER_FLAG: u16 = 0x8000;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayHeader {
+    pub magic: String,
+    pub header_size: u32,
+    pub compressed_size: u32,
+    pub header_version: u32,
+    pub decompressed_size: u32,
+    pub num_data_blocks: u32,
+    pub product_id: String,
+    pub patch_version: u32,
+    pub build_number: u16,
+    pub flags: u16,
+    pub duration_ms: u32,
+    pub crc32: u32
+}
+
+impl ReplayHeader {
+    pub fn is_multiplayer(&self) -> bool {
+        return self.flags & MULTIPLAYER_FLAG != 0;
+    }
+
+    pub fn total_length(&self) -> usize {
+        return match self.header_version {
+            0 => 64,
+            1 => 68,
+            _ => 68 // Unknown version - try 68
+        };
+    }
+}
+
+// 2.0 [Header] + 2.1 [SubHeader], returned along with its raw bytes
+pub(crate) fn read_header<R: Read>(reader: &mut R, options: &ParseOptions) -> Result<(ReplayHeader, Vec<u8>), ReplayError> {
+    let mut raw_header: Vec<u8> = vec![];
+    reader.by_ref().take(48).read_to_end(&mut raw_header).map_err(|_| ReplayError::TruncatedHeader { length: 0 })?;
+    if raw_header.len() < 48 {
+        return Err(ReplayError::TruncatedHeader { length: raw_header.len() });
+    }
+    let header = &raw_header[..48];
+    info!("Replay header: {:?}", header);
+    if !header.starts_with(REPLAY_MAGIC) {
+        return Err(ReplayError::BadMagic);
+    }
+
+    let mut replay_header = ReplayHeader {
+        magic: String::from_utf8_lossy(&header[..REPLAY_MAGIC.len() - 1]).to_string(),
+        header_size: parse_dword(&header[0x1C..0x20]),
+        compressed_size: parse_dword(&header[0x20..0x24]),
+        header_version: parse_dword(&header[0x24..0x28]),
+        decompressed_size: parse_dword(&header[0x28..0x2C]),
+        num_data_blocks: parse_dword(&header[0x2C..0x30]),
+        product_id: String::from("WAR3"),
+        patch_version: 0,
+        build_number: 0,
+        flags: 0,
+        duration_ms: 0,
+        crc32: 0
+    };
+
+    let total_length = replay_header.total_length();
+    reader.by_ref().take((total_length - 48) as u64).read_to_end(&mut raw_header).map_err(|_| ReplayError::TruncatedHeader { length: 48 })?;
+    if raw_header.len() < total_length {
+        return Err(ReplayError::TruncatedHeader { length: raw_header.len() });
+    }
+    let subheader = &raw_header[48..];
+
+    if replay_header.header_version == 0 {
+        replay_header.patch_version = parse_word(&subheader[0x02..0x04]) as u32;
+        replay_header.build_number = parse_word(&subheader[0x04..0x06]);
+        replay_header.flags = parse_word(&subheader[0x06..0x08]);
+        replay_header.duration_ms = parse_dword(&subheader[0x08..0x0C]);
+        replay_header.crc32 = parse_dword(&subheader[0x0C..0x10]);
+    }
+    else {
+        // Product id is stored reversed, e.g. "PX3W"
+        replay_header.product_id = subheader[0x00..0x04].iter().rev().map(|c| *c as char).collect();
+        replay_header.patch_version = parse_dword(&subheader[0x04..0x08]);
+        replay_header.build_number = parse_word(&subheader[0x08..0x0A]);
+        replay_header.flags = parse_word(&subheader[0x0A..0x0C]);
+        replay_header.duration_ms = parse_dword(&subheader[0x0C..0x10]);
+        replay_header.crc32 = parse_dword(&subheader[0x10..0x14]);
+    }
+    info!("Parsed replay header: {:?}", replay_header);
+
+    if options.verify_checksums {
+        let actual = header_checksum(&raw_header);
+        if actual != replay_header.crc32 {
+            return Err(ReplayError::HeaderChecksumMismatch { expected: replay_header.crc32, actual });
+        }
+    }
+
+    return Ok((replay_header, raw_header));
+}
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = Crc::new();
+    for chunk in chunks {
+        crc.update(chunk);
+    }
+    return crc.sum();
+}
+
+// CRC32 of the complete header with the CRC field set to 0
+pub(crate) fn header_checksum(raw_header: &[u8]) -> u32 {
+    let crc_offset = raw_header.len() - 4;
+    return crc32(&[&raw_header[..crc_offset], &[0u8; 4]]);
+}
+
+// Low word: folded CRC32 of the block header with the checksum set to 0
+// High word: folded CRC32 of the compressed block data
+pub(crate) fn block_checksum(block_header: &[u8], block_data: &[u8]) -> u32 {
+    let crc_header = crc32(&[&block_header[..8], &[0u8; 4]]);
+    let crc_data = crc32(&[block_data]);
+    let crc_header = (crc_header ^ (crc_header >> 16)) & 0xFFFF;
+    let crc_data = (crc_data ^ (crc_data >> 16)) & 0xFFFF;
+    return crc_header | (crc_data << 16);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, Replay};
+    use crate::testing::{summary, write};
+
+    // Expected values computed independently with zlib.crc32
+    #[test]
+    fn checksums_match_crc32() {
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xCBF43926);
+        assert_eq!(header_checksum(b"Warcraft III recorded game\x1A\x00\xFF\xFF\xFF\xFF"), 0xD11F0FFC);
+        assert_eq!(block_checksum(&[0, 1, 2, 3, 4, 5, 6, 7, 0xFF, 0xFF, 0xFF, 0xFF], b"123456789"), 0xF2D2DE2F);
+    }
+
+    #[test]
+    fn mismatches_expect_the_stored_checksum() {
+        let options = ParseOptions { verify_checksums: true, ..Default::default() };
+        let bytes = write(&summary(&["Alice#123"]), &[]);
+        let header_crc = parse_dword(&bytes[64..68]);
+        let block_crc = parse_dword(&bytes[76..80]);
+
+        let mut tampered = bytes.clone();
+        tampered[64..68].copy_from_slice(&0x12345678u32.to_le_bytes());
+        assert!(matches!(Replay::try_from_bytes_with_options(&tampered, &options),
+            Err(ReplayError::HeaderChecksumMismatch { expected: 0x12345678, actual }) if actual == header_crc));
+
+        let mut tampered = bytes.clone();
+        tampered[76..80].copy_from_slice(&0x12345678u32.to_le_bytes());
+        assert!(matches!(Replay::try_from_bytes_with_options(&tampered, &options),
+            Err(ReplayError::BlockChecksumMismatch { block: 0, offset: 68, expected: 0x12345678, actual }) if actual == block_crc));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{ability_hero, ActionKind, ItemId, ObjectCategory, ObjectRace, Replay};
+
+pub const MAX_HERO_LEVEL: u8 = 10;
+
+#[derive(Serialize, Debug)]
+pub struct HeroSkill {
+    pub timestamp: u64,
+    pub ability_id: String,
+    /// Derived hero level after learning the skill
+    pub level: u8
+}
+
+#[derive(Serialize, Debug)]
+pub struct Hero {
+    pub hero_id: String,
+    /// Not set for heroes the player did not train, e.g. with random heroes enabled
+    pub picked_at: Option<u64>,
+    pub is_tavern_hero: bool,
+    pub revivals: Vec<u64>,
+    /// Skill order
+    pub skills: Vec<HeroSkill>,
+    pub level: u8
+}
+
+impl Hero {
+    fn new(hero_id: &str, picked_at: Option<u64>) -> Hero {
+        let is_tavern_hero = ItemId::FourCC(hero_id.to_string()).object_info()
+            .is_some_and(|info| info.race == ObjectRace::NEUTRAL);
+        return Hero {
+            hero_id: hero_id.to_string(),
+            picked_at,
+            is_tavern_hero,
+            revivals: vec![],
+            skills: vec![],
+            level: 1
+        };
+    }
+
+    /// Derived hero level at the given game time.
+    pub fn level_at(&self, timestamp: u64) -> u8 {
+        let learned = self.skills.iter().filter(|s| s.timestamp <= timestamp).count() as u8;
+        return learned.clamp(1, MAX_HERO_LEVEL);
+    }
+}
+
+impl Replay {
+    /// Heroes of every player in pick order.
+    ///
+    /// Every level grants a skill point, so the hero level is derived from the number of skills learned.
+    /// Training a hero the player already owns is counted as a revival.
+    pub fn heroes(&self) -> HashMap<u8, Vec<Hero>> {
+        let mut heroes: HashMap<u8, Vec<Hero>> = HashMap::new();
+        for action in &self.actions {
+            let player_heroes = heroes.entry(action.player_id).or_default();
+            match &action.kind {
+                ActionKind::AbilityBasic { item_id: item_id @ ItemId::FourCC(fourcc), .. } => {
+                    match item_id.category() {
+                        ObjectCategory::HERO => {
+                            match player_heroes.iter_mut().find(|h| h.hero_id == *fourcc) {
+                                Some(hero) => hero.revivals.push(action.timestamp),
+                                None => player_heroes.push(Hero::new(fourcc, Some(action.timestamp)))
+                            }
+                        },
+                        ObjectCategory::ABILITY => {
+                            let Some(hero_id) = ability_hero(fourcc) else {
+                                continue;
+                            };
+                            let hero = match player_heroes.iter().position(|h| h.hero_id == hero_id) {
+                                Some(index) => &mut player_heroes[index],
+                                None => {
+                                    player_heroes.push(Hero::new(hero_id, None));
+                                    player_heroes.last_mut().unwrap()
+                                }
+                            };
+                            if hero.skills.len() >= MAX_HERO_LEVEL as usize {
+                                continue;
+                            }
+                            hero.skills.push(HeroSkill {
+                                timestamp: action.timestamp,
+                                ability_id: fourcc.clone(),
+                                level: hero.skills.len() as u8 + 1
+                            });
+                            hero.level = hero.skills.len() as u8;
+                        },
+                        _ => {}
+                    }
+                },
+                // Undo picks and revivals which were cancelled
+                ActionKind::RemoveFromQueue { item_id: ItemId::FourCC(fourcc), .. } => {
+                    let Some(index) = player_heroes.iter().position(|h| h.hero_id == *fourcc) else {
+                        continue;
+                    };
+                    let hero = &mut player_heroes[index];
+                    if hero.revivals.pop().is_none() && hero.skills.is_empty() {
+                        player_heroes.remove(index);
+                    }
+                },
+                _ => {}
+            }
+        }
+        heroes.retain(|_, player_heroes| !player_heroes.is_empty());
+        return heroes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{replay, summary, time_slot};
+
+    fn ability(id: &str) -> ActionKind {
+        return ActionKind::AbilityBasic { flags: 0, item_id: ItemId::FourCC(id.to_string()), unknown_a: 0, unknown_b: 0 };
+    }
+
+    fn cancel(id: &str) -> ActionKind {
+        return ActionKind::RemoveFromQueue { slot: 0, item_id: ItemId::FourCC(id.to_string()) };
+    }
+
+    #[test]
+    fn levels_from_skills() {
+        let replay = replay(&summary(&["Alice#123"]), &[
+            time_slot(100, 1, vec![ability("Hpal")]),
+            time_slot(100, 1, vec![ability("AHhb")]),
+            time_slot(100, 1, vec![ability("AHds"), ability("AHhb")]),
+            // Skills beyond the maximum level are ignored
+            time_slot(100, 1, (0..10).map(|_| ability("AHad")).collect())
+        ]);
+        let heroes = replay.heroes();
+        let hero = &heroes[&1][0];
+        assert_eq!((hero.hero_id.as_str(), hero.picked_at, hero.is_tavern_hero), ("Hpal", Some(100), false));
+        let skills: Vec<(&str, u8)> = hero.skills.iter().take(3).map(|s| (s.ability_id.as_str(), s.level)).collect();
+        assert_eq!(skills, [("AHhb", 1), ("AHds", 2), ("AHhb", 3)]);
+        assert_eq!(hero.skills.len(), MAX_HERO_LEVEL as usize);
+        assert_eq!(hero.level, MAX_HERO_LEVEL);
+        assert_eq!((hero.level_at(100), hero.level_at(200), hero.level_at(300)), (1, 1, 3));
+    }
+
+    #[test]
+    fn revivals_and_cancelled_picks() {
+        let replay = replay(&summary(&["Alice#123"]), &[
+            time_slot(100, 1, vec![ability("Hamg"), ability("Nalc")]),
+            time_slot(100, 1, vec![ability("Hamg")]),
+            time_slot(100, 1, vec![ability("Hamg"), cancel("Hamg")]),
+            // Cancelling the only pick of a hero removes it
+            time_slot(100, 1, vec![cancel("Nalc")])
+        ]);
+        let heroes = replay.heroes();
+        let hero_ids: Vec<&str> = heroes[&1].iter().map(|h| h.hero_id.as_str()).collect();
+        assert_eq!(hero_ids, ["Hamg"]);
+        assert_eq!(heroes[&1][0].revivals, [200]);
+    }
+
+    #[test]
+    fn skills_of_heroes_not_picked() {
+        let replay = replay(&summary(&["Alice#123"]), &[time_slot(100, 1, vec![ability("ANhs")])]);
+        let heroes = replay.heroes();
+        let hero = &heroes[&1][0];
+        assert_eq!((hero.hero_id.as_str(), hero.picked_at, hero.is_tavern_hero, hero.level), ("Nalc", None, true, 1));
+    }
+}
@@ -1,12 +1,52 @@
+#![allow(non_camel_case_types, clippy::needless_return)]
+
+mod anonymise;
+mod apm;
+mod blocks;
+mod buildorder;
+mod clip;
+mod decoder;
+mod error;
+mod events;
+mod header;
+mod heroes;
+mod objects;
+mod raw;
+mod reforged;
+mod results;
+mod saver;
+mod selection;
+#[cfg(test)]
+mod testing;
+mod w3mmd;
+mod writer;
+
 use std::collections::HashMap;
-use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
-use flate2::{Decompress, FlushDecompress};
-use log::{info, warn};
+use std::io::{BufRead, Cursor, Read, Write};
+use log::info;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use serde::{Serialize};
 
-#[derive(Serialize, FromPrimitive, Debug)]
+pub use anonymise::{anonymise, AnonymiseOptions, Anonymiser};
+pub use apm::{ApmOptions, PlayerApm};
+pub use buildorder::BuildOrderEntry;
+pub use clip::{clip, Moment, MomentKind};
+pub use decoder::ReplayDecoder;
+pub use error::ReplayError;
+pub use events::{ReplayEvent, ReplayEvents, ReplayVisitor};
+pub use header::ReplayHeader;
+pub use heroes::{Hero, HeroSkill, MAX_HERO_LEVEL};
+pub use objects::{ability_hero, hero_abilities, object_category, object_info, order_info, ObjectCategory, ObjectInfo, ObjectRace, OrderInfo, OBJECT_DATABASE_VERSION};
+pub use raw::{RawRecord, RawReplay};
+pub use reforged::{ProtobufField, ProtobufValue, ReforgedPlayerMetadata, ReforgedRecord};
+pub use results::{GameOutcome, MatchResult, PlayerResult, ResultConfidence, TeamResult};
+pub use saver::{SavingPlayerMethod, HOST_BOT_NAMES};
+pub use selection::{PlayerSelection, SelectionTracker, Subgroup, NUM_CONTROL_GROUPS};
+pub use w3mmd::{W3mmdData, W3mmdEvent, W3mmdEventDefinition, W3mmdFlag, W3mmdGoal, W3mmdPlayer, W3mmdSuggestion, W3mmdType, W3mmdValue, W3mmdVariable};
+pub use writer::{ChatFilterResult, ReplayWriter};
+
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy)]
 pub enum SlotColor {
     RED = 1,
     BLUE = 2,
@@ -36,18 +76,18 @@ pub enum SlotColor {
     UNKNOWN = 127
 }
 
-#[derive(Serialize, FromPrimitive, Debug)]
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy)]
 pub enum SlotRace {
     HUMAN = 1,
     ORC = 2,
     NIGHTELF = 4,
     UNDEAD = 8,
-    RANDOM = 20,
-    FIXED = 40,
+    RANDOM = 0x20,
+    FIXED = 0x40,
     UNKNOWN = 127
 }
 
-#[derive(Serialize, FromPrimitive, Debug)]
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy)]
 pub enum ComputerAIStrength {
     EASY = 0,
     NORMAL = 1,
@@ -55,7 +95,7 @@ pub enum ComputerAIStrength {
     UNKNOWN = 127
 }
 
-#[derive(Serialize, FromPrimitive, Debug)]
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy)]
 pub enum SlotStatus {
     EMPTY = 0,
     CLOSED = 1,
@@ -63,17 +103,50 @@ pub enum SlotStatus {
     UNKNOWN = 127
 }
 
-#[derive(Serialize, FromPrimitive, Debug)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum LeaveReason {
-    CONNECTION_CLOSED_BY_REMOTE_GAME = 0x01,
-    CONNECTION_CLOSED_BY_LOCAL_GAME = 0x0C,
-    UNKNOWN
+    CONNECTION_CLOSED_BY_REMOTE_GAME,
+    CONNECTION_CLOSED_BY_LOCAL_GAME,
+    /// Reason as read, 0 for players without a LeaveGame record
+    UNKNOWN(u32)
+}
+
+impl LeaveReason {
+    pub fn from_u32(value: u32) -> LeaveReason {
+        return match value {
+            0x01 => LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME,
+            0x0C => LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME,
+            _ => LeaveReason::UNKNOWN(value)
+        };
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        return match self {
+            LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME => 0x01,
+            LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME => 0x0C,
+            LeaveReason::UNKNOWN(value) => *value
+        };
+    }
+}
+
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy, PartialEq)]
+pub enum LeaveResult {
+    DISCONNECTED = 0x01,
+    LEFT = 0x07,
+    LOST = 0x08,
+    WON = 0x09,
+    DRAW = 0x0A,
+    OBSERVER_LEFT = 0x0B,
+    UNKNOWN = 127
 }
 
-#[derive(Serialize, FromPrimitive, Debug, PartialEq)]
+#[derive(Serialize, FromPrimitive, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum ActionType {
     PAUSE = 0x01,
     RESUME = 0x02,
+    SET_GAME_SPEED = 0x03,
+    INCREASE_GAME_SPEED = 0x04,
+    DECREASE_GAME_SPEED = 0x05,
 
     SAVE_GAME = 0x06,
     SAVE_GAME_DONE = 0x07,
@@ -82,14 +155,59 @@ pub enum ActionType {
     ABILITY_WITH_TARGET_LOCATION = 0x11,
     ABILITY_WITH_TARGET_LOCATION_AND_OBJECT = 0x12,
     ITEM_TRANSFER = 0x13,
+    ABILITY_WITH_TWO_TARGET_LOCATIONS = 0x14,
 
     CHANGE_SELECTION = 0x16,
     GROUP_ASSIGN = 0x17,
     GROUP_SELECT = 0x18,
-
+    SELECT_SUBGROUP = 0x19,
+    PRE_SUBSELECTION = 0x1A,
+    UNKNOWN_1B = 0x1B,
+    SELECT_GROUND_ITEM = 0x1C,
+    CANCEL_HERO_REVIVAL = 0x1D,
+    REMOVE_FROM_QUEUE = 0x1E,
+
+    // Single player cheats
+    CHEAT_THE_DUDE_ABIDES = 0x20,
+    UNKNOWN_21 = 0x21,
+    CHEAT_SOMEBODY_SET_UP_US_THE_BOMB = 0x22,
+    CHEAT_WARP_TEN = 0x23,
+    CHEAT_IOCAINE_POWDER = 0x24,
+    CHEAT_POINT_BREAK = 0x25,
+    CHEAT_WHOS_YOUR_DADDY = 0x26,
+    CHEAT_KEYSER_SOZE = 0x27,
+    CHEAT_LEAFIT_TO_ME = 0x28,
+    CHEAT_THERE_IS_NO_SPOON = 0x29,
+    CHEAT_STRENGTH_AND_HONOR = 0x2A,
+    CHEAT_IT_VEXES_ME = 0x2B,
+    CHEAT_WHO_IS_JOHN_GALT = 0x2C,
+    CHEAT_GREED_IS_GOOD = 0x2D,
+    CHEAT_DAYLIGHT_SAVINGS = 0x2E,
+    CHEAT_I_SEE_DEAD_PEOPLE = 0x2F,
+    CHEAT_SYNERGY = 0x30,
+    CHEAT_SHARP_AND_SHINY = 0x31,
+    CHEAT_ALL_YOUR_BASE_ARE_BELONG_TO_US = 0x32,
+
+    CHANGE_ALLY_OPTIONS = 0x50,
+    TRANSFER_RESOURCES = 0x51,
+
+    TRIGGER_CHAT = 0x60,
+    ESC_PRESSED = 0x61,
+    SCENARIO_TRIGGER = 0x62,
+    CHOOSE_HERO_SKILL_SUBMENU = 0x66,
+    CHOOSE_BUILDING_SUBMENU = 0x67,
     MINIMAP_SIGNAL = 0x68,
+    CONTINUE_GAME_B = 0x69,
+    CONTINUE_GAME_A = 0x6A,
+    SYNC_STORE_INTEGER = 0x6B,
+    SYNC_STORE_UNKNOWN_6C = 0x6C,
+    SYNC_STORE_UNKNOWN_6D = 0x6D,
+
+    ARROW_KEY = 0x75,
+    UNKNOWN_7A = 0x7A,
+    UNKNOWN_7B = 0x7B,
 
-    UNKNOWN
+    UNKNOWN = 0xFF
 }
 
 #[derive(Serialize, Debug)]
@@ -100,14 +218,18 @@ pub struct MapLocation {
 
 #[derive(Serialize)]
 pub struct ReplayMeta {
+    /// Player id of the 4.1 PlayerRecord
+    pub record_player_id: u8,
+    pub record_player_is_host: bool,
     pub saving_player_id: u8,
+    pub saving_player_method: SavingPlayerMethod,
     pub is_saving_player_host: bool,
     pub game_name: String,
     pub map_name: String,
     pub game_creator_battle_tag: String
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Default)]
 pub struct GameSettings {
     pub game_speed: u8,
     pub vis_hide_terrain: bool,
@@ -120,10 +242,114 @@ pub struct GameSettings {
     pub shared_unit_control: bool,
     pub random_hero: bool,
     pub random_races: bool,
-    pub obs_referees: bool
+    pub obs_referees: bool,
+    pub map_checksum: u32,
+    /// Data following the creator name, e.g. the SHA-1 hash of the map
+    pub extra_data: Vec<u8>,
+    // Settings bytes 0 - 8 as read, the decoded bits are replaced when writing
+    #[serde(skip)]
+    pub(crate) raw: [u8; 9]
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameType {
+    LADDER_SOLO_OR_FFA,
+    CUSTOM,
+    SINGLE_PLAYER,
+    LADDER_TEAM,
+    /// Game type byte as read
+    UNKNOWN(u8)
+}
+
+impl Default for GameType {
+    fn default() -> GameType {
+        return GameType::UNKNOWN(0);
+    }
+}
+
+impl GameType {
+    pub fn from_u8(value: u8) -> GameType {
+        return match value {
+            0x01 => GameType::LADDER_SOLO_OR_FFA,
+            0x09 => GameType::CUSTOM,
+            0x0D => GameType::SINGLE_PLAYER,
+            0x20 => GameType::LADDER_TEAM,
+            _ => GameType::UNKNOWN(value)
+        };
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        return match self {
+            GameType::LADDER_SOLO_OR_FFA => 0x01,
+            GameType::CUSTOM => 0x09,
+            GameType::SINGLE_PLAYER => 0x0D,
+            GameType::LADDER_TEAM => 0x20,
+            GameType::UNKNOWN(value) => *value
+        };
+    }
+
+    pub fn is_ladder(&self) -> bool {
+        return matches!(self, GameType::LADDER_SOLO_OR_FFA | GameType::LADDER_TEAM);
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum SlotSelectionMode {
+    TEAM_AND_RACE_SELECTABLE,
+    TEAM_NOT_SELECTABLE,
+    TEAM_AND_RACE_NOT_SELECTABLE,
+    RACE_FIXED_TO_RANDOM,
+    AUTOMATED_MATCH_MAKING,
+    /// Selection mode byte as read
+    UNKNOWN(u8)
+}
+
+impl Default for SlotSelectionMode {
+    fn default() -> SlotSelectionMode {
+        return SlotSelectionMode::UNKNOWN(0xFF);
+    }
+}
+
+impl SlotSelectionMode {
+    pub fn from_u8(value: u8) -> SlotSelectionMode {
+        return match value {
+            0x00 => SlotSelectionMode::TEAM_AND_RACE_SELECTABLE,
+            0x01 => SlotSelectionMode::TEAM_NOT_SELECTABLE,
+            0x03 => SlotSelectionMode::TEAM_AND_RACE_NOT_SELECTABLE,
+            0x04 => SlotSelectionMode::RACE_FIXED_TO_RANDOM,
+            0xCC => SlotSelectionMode::AUTOMATED_MATCH_MAKING,
+            _ => SlotSelectionMode::UNKNOWN(value)
+        };
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        return match self {
+            SlotSelectionMode::TEAM_AND_RACE_SELECTABLE => 0x00,
+            SlotSelectionMode::TEAM_NOT_SELECTABLE => 0x01,
+            SlotSelectionMode::TEAM_AND_RACE_NOT_SELECTABLE => 0x03,
+            SlotSelectionMode::RACE_FIXED_TO_RANDOM => 0x04,
+            SlotSelectionMode::AUTOMATED_MATCH_MAKING => 0xCC,
+            SlotSelectionMode::UNKNOWN(value) => *value
+        };
+    }
+}
+
+// 4.6 - 4.8 and the fields following the slot records in 4.10
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GameStartInfo {
+    pub num_players_slots: u32,
+    pub game_type: GameType,
+    pub is_private_custom_game: bool,
+    pub language_id: u32,
+    pub random_seed: u32,
+    pub selection_mode: SlotSelectionMode,
+    pub start_spot_count: u8,
+    // Bytes 1 - 3 of 4.7 as read, the private flag is replaced when writing
+    #[serde(skip)]
+    pub(crate) game_type_raw: [u8; 3]
+}
+
+#[derive(Serialize, Clone)]
 pub struct Slot {
     pub player_id: u8,
     pub map_download_percent: u8,
@@ -132,90 +358,442 @@ pub struct Slot {
     pub team_index: u8,
     pub color: SlotColor,
     pub race: SlotRace,
+    /// Race byte of the slot record, which may combine several [`SlotRace`] flags
+    pub race_flags: u8,
     pub ai_strength: ComputerAIStrength,
-    pub handicap_percent: u8
+    pub handicap_percent: u8,
+    // Slot record as read, written back for the values decoded as UNKNOWN
+    #[serde(skip)]
+    pub(crate) raw: [u8; 9]
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ReplayPlayer {
     pub battle_tag: String,
     pub leave_reason: LeaveReason,
     pub result_byte: u8,
-    pub left_at: u64
+    pub left_at: u64,
+    pub reforged: Option<ReforgedPlayerMetadata>,
+    // Additional data of the player record, e.g. ladder data, and the dword following 0x16 records
+    #[serde(skip)]
+    pub(crate) additional_data: Vec<u8>,
+    #[serde(skip)]
+    pub(crate) record_unknown: u32
 }
 
-#[derive(Serialize, Debug)]
+impl ReplayPlayer {
+    pub fn leave_result(&self) -> LeaveResult {
+        return LeaveResult::from_u8(self.result_byte).or(Option::from(LeaveResult::UNKNOWN)).unwrap();
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum ChatChannel {
+    All,
+    Allies,
+    Observers,
+    /// Slot number of the recipient
+    Private(u8),
+    /// Sent before the game started
+    Lobby,
+    /// Text sent to the map through a 0x60 action, e.g. chat commands
+    Trigger,
+    Unknown(u32)
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct ChatMessage {
     pub sender_player_id: u8,
-    pub recipient_slot_number: Option<i8>,
+    pub channel: ChatChannel,
+    /// Resolved for private messages
+    pub recipient_player_id: Option<u8>,
     pub flag: Option<u8>,
     pub message: String,
     pub timestamp: u64
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ObjectIDs {
     pub id1: u32,
     pub id2: u32
 }
 
-#[derive(Serialize, FromPrimitive, Debug, PartialEq)]
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy, PartialEq)]
 pub enum SelectionMode {
     ADD = 0x01,
-    REMOVE = 0x02
+    REMOVE = 0x02,
+    UNKNOWN = 127
 }
 
-#[derive(Serialize)]
-#[derive(Default)]
-pub struct ActionData {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub location: Option<MapLocation>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub savegame_name: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub item_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub unknownA: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub unknownB: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub unknownC: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub objects: Option<Vec<ObjectIDs>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ability_flags: Option<u16>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sel_mode: Option<SelectionMode>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub group_id: Option<u8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    target_obj_id_1: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    target_obj_id_2: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    item_obj_id_1: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    item_obj_id_2: Option<u32>,
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum ItemId {
+    FourCC(String),
+    Order(u32)
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
+#[serde(tag = "type")]
+pub enum ActionKind {
+    Pause,
+    Resume,
+    SetGameSpeed {
+        speed: u8
+    },
+    IncreaseGameSpeed,
+    DecreaseGameSpeed,
+    SaveGame {
+        name: String
+    },
+    SaveGameDone {
+        unknown: u32
+    },
+    AbilityBasic {
+        flags: u16,
+        item_id: ItemId,
+        unknown_a: u32,
+        unknown_b: u32
+    },
+    AbilityWithTargetLocation {
+        flags: u16,
+        item_id: ItemId,
+        unknown_a: u32,
+        unknown_b: u32,
+        location: MapLocation
+    },
+    AbilityWithTargetObject {
+        flags: u16,
+        item_id: ItemId,
+        unknown_a: u32,
+        unknown_b: u32,
+        location: MapLocation,
+        target: ObjectIDs
+    },
+    ItemTransfer {
+        flags: u16,
+        item_id: ItemId,
+        unknown_a: u32,
+        unknown_b: u32,
+        location: MapLocation,
+        target: ObjectIDs,
+        item: ObjectIDs
+    },
+    AbilityWithTwoTargetLocations {
+        flags: u16,
+        item_id: ItemId,
+        unknown_a: u32,
+        unknown_b: u32,
+        location: MapLocation,
+        item_id_b: ItemId,
+        unknown: [u8; 9],
+        location_b: MapLocation
+    },
+    ChangeSelection {
+        mode: SelectionMode,
+        objects: Vec<ObjectIDs>
+    },
+    GroupAssign {
+        group: u8,
+        objects: Vec<ObjectIDs>
+    },
+    GroupSelect {
+        group: u8,
+        unknown: u8
+    },
+    SelectSubgroup {
+        item_id: ItemId,
+        object: ObjectIDs
+    },
+    PreSubselection,
+    Unknown1B {
+        unknown: u8,
+        object: ObjectIDs
+    },
+    SelectGroundItem {
+        flags: u8,
+        object: ObjectIDs
+    },
+    CancelHeroRevival {
+        hero: ObjectIDs
+    },
+    RemoveFromQueue {
+        slot: u8,
+        item_id: ItemId
+    },
+    Unknown21 {
+        unknown_a: u32,
+        unknown_b: u32
+    },
+    Cheat {
+        cheat: ActionType
+    },
+    CheatResources {
+        cheat: ActionType,
+        unknown: u8,
+        amount: u32
+    },
+    CheatDaylightSavings {
+        time_of_day: f32
+    },
+    ChangeAllyOptions {
+        slot: u8,
+        flags: u32
+    },
+    TransferResources {
+        slot: u8,
+        gold: u32,
+        lumber: u32
+    },
+    TriggerChat {
+        unknown_a: u32,
+        unknown_b: u32,
+        message: String
+    },
+    EscPressed,
+    ScenarioTrigger {
+        unknown_a: u32,
+        unknown_b: u32,
+        unknown_c: u32
+    },
+    ChooseHeroSkillSubmenu,
+    ChooseBuildingSubmenu,
+    MinimapSignal {
+        location: MapLocation,
+        unknown: u32
+    },
+    ContinueGameB {
+        unknown: [u32; 4]
+    },
+    ContinueGameA {
+        unknown: [u32; 4]
+    },
+    SyncStoreInteger {
+        filename: String,
+        mission_key: String,
+        key: String,
+        value: u32
+    },
+    SyncStoreUnknown6C {
+        filename: String,
+        mission_key: String,
+        key: String
+    },
+    SyncStoreUnknown6D {
+        filename: String,
+        mission_key: String,
+        key: String,
+        value: u32
+    },
+    ArrowKey {
+        key: u8
+    },
+    Unknown7A {
+        unknown: [u32; 5]
+    },
+    Unknown7B {
+        unknown: [u32; 4]
+    }
+}
+
+impl ActionKind {
+    pub fn action_type(&self) -> ActionType {
+        return match self {
+            ActionKind::Pause => ActionType::PAUSE,
+            ActionKind::Resume => ActionType::RESUME,
+            ActionKind::SetGameSpeed { .. } => ActionType::SET_GAME_SPEED,
+            ActionKind::IncreaseGameSpeed => ActionType::INCREASE_GAME_SPEED,
+            ActionKind::DecreaseGameSpeed => ActionType::DECREASE_GAME_SPEED,
+            ActionKind::SaveGame { .. } => ActionType::SAVE_GAME,
+            ActionKind::SaveGameDone { .. } => ActionType::SAVE_GAME_DONE,
+            ActionKind::AbilityBasic { .. } => ActionType::ABILITY_BASIC,
+            ActionKind::AbilityWithTargetLocation { .. } => ActionType::ABILITY_WITH_TARGET_LOCATION,
+            ActionKind::AbilityWithTargetObject { .. } => ActionType::ABILITY_WITH_TARGET_LOCATION_AND_OBJECT,
+            ActionKind::ItemTransfer { .. } => ActionType::ITEM_TRANSFER,
+            ActionKind::AbilityWithTwoTargetLocations { .. } => ActionType::ABILITY_WITH_TWO_TARGET_LOCATIONS,
+            ActionKind::ChangeSelection { .. } => ActionType::CHANGE_SELECTION,
+            ActionKind::GroupAssign { .. } => ActionType::GROUP_ASSIGN,
+            ActionKind::GroupSelect { .. } => ActionType::GROUP_SELECT,
+            ActionKind::SelectSubgroup { .. } => ActionType::SELECT_SUBGROUP,
+            ActionKind::PreSubselection => ActionType::PRE_SUBSELECTION,
+            ActionKind::Unknown1B { .. } => ActionType::UNKNOWN_1B,
+            ActionKind::SelectGroundItem { .. } => ActionType::SELECT_GROUND_ITEM,
+            ActionKind::CancelHeroRevival { .. } => ActionType::CANCEL_HERO_REVIVAL,
+            ActionKind::RemoveFromQueue { .. } => ActionType::REMOVE_FROM_QUEUE,
+            ActionKind::Unknown21 { .. } => ActionType::UNKNOWN_21,
+            ActionKind::Cheat { cheat } => *cheat,
+            ActionKind::CheatResources { cheat, .. } => *cheat,
+            ActionKind::CheatDaylightSavings { .. } => ActionType::CHEAT_DAYLIGHT_SAVINGS,
+            ActionKind::ChangeAllyOptions { .. } => ActionType::CHANGE_ALLY_OPTIONS,
+            ActionKind::TransferResources { .. } => ActionType::TRANSFER_RESOURCES,
+            ActionKind::TriggerChat { .. } => ActionType::TRIGGER_CHAT,
+            ActionKind::EscPressed => ActionType::ESC_PRESSED,
+            ActionKind::ScenarioTrigger { .. } => ActionType::SCENARIO_TRIGGER,
+            ActionKind::ChooseHeroSkillSubmenu => ActionType::CHOOSE_HERO_SKILL_SUBMENU,
+            ActionKind::ChooseBuildingSubmenu => ActionType::CHOOSE_BUILDING_SUBMENU,
+            ActionKind::MinimapSignal { .. } => ActionType::MINIMAP_SIGNAL,
+            ActionKind::ContinueGameB { .. } => ActionType::CONTINUE_GAME_B,
+            ActionKind::ContinueGameA { .. } => ActionType::CONTINUE_GAME_A,
+            ActionKind::SyncStoreInteger { .. } => ActionType::SYNC_STORE_INTEGER,
+            ActionKind::SyncStoreUnknown6C { .. } => ActionType::SYNC_STORE_UNKNOWN_6C,
+            ActionKind::SyncStoreUnknown6D { .. } => ActionType::SYNC_STORE_UNKNOWN_6D,
+            ActionKind::ArrowKey { .. } => ActionType::ARROW_KEY,
+            ActionKind::Unknown7A { .. } => ActionType::UNKNOWN_7A,
+            ActionKind::Unknown7B { .. } => ActionType::UNKNOWN_7B
+        };
+    }
+}
+
+#[derive(Serialize, Debug)]
 pub struct Action {
     pub player_id: u8,
     pub timestamp: u64,
-    pub action_type: ActionType,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub data: Option<ActionData>
+    #[serde(flatten)]
+    pub kind: ActionKind,
+    /// Units an ability action was issued to, see [`SelectionTracker`].
+    pub units: Option<Vec<ObjectIDs>>
+}
+
+impl Action {
+    pub fn action_type(&self) -> ActionType {
+        return self.kind.action_type();
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommandBlock {
+    pub player_id: u8,
+    pub actions: Vec<Action>
+}
+
+#[derive(Serialize, Debug)]
+pub struct TimeSlot {
+    pub timestamp: u64,
+    pub increment: u16,
+    pub command_blocks: Vec<CommandBlock>
+}
+
+// TimeSlot kept by the Replay for writing, its actions are in Replay::actions and Replay::chat
+#[derive(Debug, Clone)]
+pub(crate) struct TimeSlotLayout {
+    pub(crate) timestamp: u64,
+    pub(crate) increment: u16,
+    pub(crate) command_blocks: Vec<CommandBlockLayout>
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CommandBlockLayout {
+    pub(crate) player_id: u8,
+    // Number of actions other than trigger chat
+    pub(crate) actions: usize,
+    // Unknown values of the 0x60 trigger chat actions
+    pub(crate) trigger_chat: Vec<(u32, u32)>
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PlayerLeave {
+    pub player_id: u8,
+    pub reason: LeaveReason,
+    pub result: u32,
+    pub unknown: u32,
+    pub timestamp: u64
+}
+
+// 0x22, 0x23 and 0x2F: records without decoded view, e.g. the forced game end countdown (0x2F)
+#[derive(Serialize, Debug, Clone)]
+pub struct OtherRecord {
+    pub id: u8,
+    /// Record data following the id
+    pub data: Vec<u8>,
+    pub timestamp: u64
+}
+
+#[derive(Serialize, FromPrimitive, Debug, Clone, Copy, PartialEq)]
+pub enum StartupRecordType {
+    FIRST = 0x1A,
+    SECOND = 0x1B,
+    THIRD = 0x1C,
+    UNKNOWN = 127
+}
+
+// 0x1A - 0x1C: sent while the game is loading, before the first TimeSlot
+#[derive(Serialize, Debug, Clone)]
+pub struct StartupRecord {
+    pub record_type: StartupRecordType,
+    pub value: u32,
+    pub timestamp: u64
+}
+
+#[derive(Serialize, Debug)]
+pub enum ReplayRecord {
+    Startup(StartupRecord),
+    LeaveGame(PlayerLeave),
+    TimeSlot(TimeSlot),
+    Chat(ChatMessage),
+    Other(OtherRecord)
 }
 
 #[derive(Serialize)]
 pub struct Replay {
     pub version: u8,
+    pub header: ReplayHeader,
     pub metadata: ReplayMeta,
     pub game_settings: GameSettings,
+    pub game_start_info: GameStartInfo,
     pub slots: Vec<Slot>,
     pub players: HashMap<u8, ReplayPlayer>,
+    /// Reforged 0x39 records which belong to no known player
+    pub reforged_records: Vec<ReforgedRecord>,
     pub chat: Vec<ChatMessage>,
-    pub actions: Vec<Action>
+    pub actions: Vec<Action>,
+    /// LeaveGame records in the order the players left
+    pub leaves: Vec<PlayerLeave>,
+    pub startup_records: Vec<StartupRecord>,
+    /// Timestamp at which the first TimeSlot with actions starts, see [`ReplayDecoder::game_start`]
+    pub game_start: u64,
+    pub raw: Option<RawReplay>,
+    // Records not kept elsewhere, written back by Replay::write_to
+    #[serde(skip)]
+    pub(crate) time_slots: Vec<TimeSlotLayout>,
+    #[serde(skip)]
+    pub(crate) other_records: Vec<OtherRecord>
+}
+
+#[derive(Serialize)]
+pub struct ReplaySummary {
+    pub version: u8,
+    pub header: ReplayHeader,
+    /// Player id of the 4.1 PlayerRecord
+    pub record_player_id: u8,
+    pub record_player_is_host: bool,
+    pub game_name: String,
+    pub map_name: String,
+    pub game_creator_battle_tag: String,
+    pub game_settings: GameSettings,
+    pub game_start_info: GameStartInfo,
+    pub slots: Vec<Slot>,
+    pub players: HashMap<u8, ReplayPlayer>,
+    /// Reforged 0x39 records which belong to no known player
+    pub reforged_records: Vec<ReforgedRecord>
+}
+
+struct GameInfo {
+    player_is_host: bool,
+    record_player_id: u8,
+    game_name: String,
+    map_name: String,
+    game_creator_name: String,
+    game_settings: GameSettings,
+    game_start_info: GameStartInfo,
+    slots: Vec<Slot>,
+    players: HashMap<u8, ReplayPlayer>,
+    reforged_records: Vec<ReforgedRecord>
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct ParseOptions {
+    pub verify_checksums: bool,
+    /// Keep the original bytes of the header and all records, see [`RawReplay`]
+    pub keep_raw_records: bool
 }
 
 fn parse_dword(bytes: &[u8]) -> u32 {
@@ -234,72 +812,101 @@ fn parse_word(bytes: &[u8]) -> u16 {
     return data;
 }
 
-fn cursor_read_dword<T>(cursor: &mut Cursor<T>) -> u32 where T: AsRef<[u8]> {
+fn cursor_read_exact<T>(cursor: &mut Cursor<T>, buf: &mut [u8]) -> Result<(), ReplayError> where T: AsRef<[u8]> {
+    let offset = cursor.position();
+    return cursor.read_exact(buf).map_err(|_| ReplayError::UnexpectedEof { offset });
+}
+
+fn cursor_read_dword<T>(cursor: &mut Cursor<T>) -> Result<u32, ReplayError> where T: AsRef<[u8]> {
     let mut buf = [0u8; 4];
-    cursor.read_exact(&mut buf).unwrap();
-    return parse_dword(&buf);
+    cursor_read_exact(cursor, &mut buf)?;
+    return Ok(parse_dword(&buf));
 }
 
-fn cursor_read_dword_float<T>(cursor: &mut Cursor<T>) -> f32 where T: AsRef<[u8]> {
+fn cursor_read_dword_float<T>(cursor: &mut Cursor<T>) -> Result<f32, ReplayError> where T: AsRef<[u8]> {
     let mut buf = [0u8; 4];
-    cursor.read_exact(&mut buf).unwrap();
+    cursor_read_exact(cursor, &mut buf)?;
     buf.reverse();
-    return f32::from_be_bytes(buf);
+    return Ok(f32::from_be_bytes(buf));
 }
 
-fn cursor_read_word<T>(cursor: &mut Cursor<T>) -> u16 where T: AsRef<[u8]> {
+fn cursor_read_word<T>(cursor: &mut Cursor<T>) -> Result<u16, ReplayError> where T: AsRef<[u8]> {
     let mut buf = [0u8; 2];
-    cursor.read_exact(&mut buf).unwrap();
-    return parse_word(&buf);
+    cursor_read_exact(cursor, &mut buf)?;
+    return Ok(parse_word(&buf));
 }
 
-fn cursor_read_nullterminated_string<T>(cursor: &mut Cursor<T>) -> String where T: AsRef<[u8]> {
+fn cursor_read_nullterminated_string<T>(cursor: &mut Cursor<T>) -> Result<String, ReplayError> where T: AsRef<[u8]> {
+    let offset = cursor.position();
     let mut string_buf: Vec<u8> = vec![];
-    cursor.read_until(0x00, &mut string_buf).unwrap();
+    cursor.read_until(0x00, &mut string_buf).map_err(|_| ReplayError::UnexpectedEof { offset })?;
+    if string_buf.last() != Some(&0x00) {
+        return Err(ReplayError::UnexpectedEof { offset });
+    }
 
     let string = String::from_utf8_lossy(&string_buf[..string_buf.len()-1]);
-    return string.to_string()
-}
-
-fn cursor_read_string<T>(cursor: &mut Cursor<T>, len: usize) -> String where T: AsRef<[u8]> {
-    let mut string_buf: Vec<u8> = vec![0u8; len];
-    cursor.read_exact(&mut string_buf).unwrap();
-    let string = String::from_utf8_lossy(&string_buf);
-    info!("Read string: {:?} {}", string_buf, string);
-    return string.to_string();
+    return Ok(string.to_string())
 }
 
-pub fn cursor_read_byte<T>(cursor: &mut Cursor<T>) -> u8 where T: AsRef<[u8]> {
+pub fn cursor_read_byte<T>(cursor: &mut Cursor<T>) -> Result<u8, ReplayError> where T: AsRef<[u8]> {
     let mut buf: [u8;1] = [0u8];
-    cursor.read_exact(&mut buf).unwrap();
-    return buf[0];
+    cursor_read_exact(cursor, &mut buf)?;
+    return Ok(buf[0]);
 }
 
-fn cursor_skip_bytes<T>(cursor: &mut Cursor<T>, n: i64) where T: AsRef<[u8]> {
-    cursor.seek(SeekFrom::Current(n)).unwrap();
+fn cursor_skip_bytes<T>(cursor: &mut Cursor<T>, n: i64) -> Result<(), ReplayError> where T: AsRef<[u8]> {
+    let offset = cursor.position();
+    let len = cursor.get_ref().as_ref().len() as u64;
+    match offset.checked_add_signed(n) {
+        Some(target) if target <= len => {
+            cursor.set_position(target);
+            return Ok(());
+        }
+        _ => return Err(ReplayError::UnexpectedEof { offset })
+    }
 }
 
-fn cursor_read_ability_itemid<T>(cursor: &mut Cursor<T>) -> String where T: AsRef<[u8]> {
-    let item_id: String;
-    let item_id_end = cursor_read_word(cursor);
-    cursor_skip_bytes(cursor, -4);
+fn cursor_read_ability_itemid<T>(cursor: &mut Cursor<T>) -> Result<ItemId, ReplayError> where T: AsRef<[u8]> {
+    let mut buf = [0u8; 4];
+    cursor_read_exact(cursor, &mut buf)?;
 
-    if item_id_end == 0x000D {
-        item_id = cursor_read_string(cursor, 2);
-        cursor_skip_bytes(cursor, 2);
+    // Order ids (smart, move, attack, ...) are stored as 0x000D____
+    if parse_word(&buf[2..4]) == 0x000D {
+        return Ok(ItemId::Order(parse_dword(&buf)));
     }
-    else {
-        item_id = cursor_read_string(cursor, 4);
+
+    // FourCC codes are stored reversed
+    buf.reverse();
+    return Ok(ItemId::FourCC(String::from_utf8_lossy(&buf).to_string()));
+}
+
+fn cursor_read_location<T>(cursor: &mut Cursor<T>) -> Result<MapLocation, ReplayError> where T: AsRef<[u8]> {
+    return Ok(MapLocation {
+        x: cursor_read_dword_float(cursor)?,
+        y: cursor_read_dword_float(cursor)?
+    });
+}
+
+fn cursor_read_dwords<T, const N: usize>(cursor: &mut Cursor<T>) -> Result<[u32; N], ReplayError> where T: AsRef<[u8]> {
+    let mut dwords = [0u32; N];
+    for dword in dwords.iter_mut() {
+        *dword = cursor_read_dword(cursor)?;
     }
+    return Ok(dwords);
+}
 
-    return item_id;
+fn cursor_read_object_ids<T>(cursor: &mut Cursor<T>) -> Result<ObjectIDs, ReplayError> where T: AsRef<[u8]> {
+    return Ok(ObjectIDs {
+        id1: cursor_read_dword(cursor)?,
+        id2: cursor_read_dword(cursor)?
+    });
 }
 
-fn decode_gamesettings(enc: &Vec<u8>) -> Vec<u8> {
+fn decode_gamesettings(enc: &[u8]) -> Vec<u8> {
     let mut i = 0;
     let mut mask: u8 = 0;
     let mut dec: Vec<u8> = vec![];
-    while enc[i] != 0 {
+    while i < enc.len() && enc[i] != 0 {
         if i % 8 == 0 { mask = enc[i]; }
         else {
             if mask & (0x1 << (i%8)) == 0 {
@@ -332,697 +939,133 @@ fn get_bits_value(byte: u8, bits: &[u8]) -> u8 {
 
 impl Replay {
     pub fn from_bytes(bytes: &[u8]) -> Replay {
-        let mut reader = Cursor::new(bytes);
-        info!("Total bytes length: {:?}", bytes.len());
-        let mut header: [u8; 48] = [0; 48];
-        reader.read_exact(&mut header).unwrap();
-        info!("Replay version: {:?}", header);
-        let version = header.get(0x0024).unwrap();
-        let total_header_length = match version {
-            0 => 64,
-            1 => 68,
-            _ => 68 // Unknown version - try 68
-        };
+        return Replay::try_from_bytes(bytes).unwrap_or_else(|e| panic!("Failed to parse replay: {}", e));
+    }
 
-        let mut subheader: Vec<u8> = vec![0; total_header_length - 48];
-        reader.read_exact(&mut subheader).unwrap();
+    /// Game time of a timestamp, relative to the actual game start.
+    pub fn game_time(&self, timestamp: u64) -> u64 {
+        return timestamp.saturating_sub(self.game_start);
+    }
 
-        let mut i: u32 = total_header_length as u32;
-        let mut k = 0;
-        let num_data_blocks = parse_dword(&header[44..48]);
-        info!("Total data blocks: {:?}", num_data_blocks);
-        let mut block_header: [u8; 12] = [0; 12];
-        let mut data: Vec<u8> = vec![];
+    /// Encodes the replay with a [`ReplayWriter`], see [`ReplayWriter::from_replay`].
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), ReplayError> {
+        return ReplayWriter::from_replay(self)?.finish(writer);
+    }
 
-        while k < num_data_blocks {
-            // 3.0 [Data block header]
-            match reader.read_exact(&mut block_header) {
-                Ok(_) => {
-                    let block_data_length_bytes: &[u8] = block_header.get(0..4).unwrap();
-                    let block_data_length_inflated_bytes: &[u8] = block_header.get(4..8).unwrap();
-                    let block_data_length = parse_dword(block_data_length_bytes);
-                    let block_data_length_inflated = parse_dword(block_data_length_inflated_bytes);
+    /// Messages sent in the lobby and while the game was loading.
+    pub fn pre_game_chat(&self) -> impl Iterator<Item = &ChatMessage> {
+        return self.chat.iter().filter(|m| m.channel == ChatChannel::Lobby);
+    }
 
-                    let crc_deflated = parse_word(block_header.get(8..10).unwrap());
-                    let crc_inflated = parse_word(block_header.get(10..12).unwrap());
-                    let mut decoder = Decompress::new(true);
+    /// Reads only the header and the records in front of the ReplayData (4.1 - 4.10),
+    /// inflating no more data blocks than necessary.
+    pub fn peek(bytes: &[u8]) -> Result<ReplaySummary, ReplayError> {
+        return Ok(ReplayDecoder::new(bytes)?.into_summary());
+    }
 
-                    info!("Word at offset {:#06x} ({:?}) {:?} ({:?}) / inflated: {:?} ({:?})", i, i, block_data_length_bytes, block_data_length, block_data_length_inflated_bytes, block_data_length_inflated);
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        return Replay::try_from_bytes_with_options(bytes, &ParseOptions::default());
+    }
 
-                    let mut block_data: Vec<u8> = vec![0; block_data_length as usize];
-                    match reader.read_exact(&mut block_data) {
-                        Ok(_) => {
-                            info!("Read datablock of length {:?}.", block_data_length);
+    pub fn try_from_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Replay, ReplayError> {
+        info!("Total bytes length: {:?}", bytes.len());
+        return Replay::from_reader_with_options(bytes, options);
+    }
 
-                            let mut out: Vec<u8> = Vec::with_capacity(block_data_length_inflated as usize);
+    pub fn from_reader<R: Read>(reader: R) -> Result<Replay, ReplayError> {
+        return Replay::from_reader_with_options(reader, &ParseOptions::default());
+    }
 
-                            // 4.0 [Decompressed data]
-                            decoder.decompress_vec(&block_data, &mut out, FlushDecompress::Sync).unwrap();
-                            decoder.reset(true);
-                            info!("Decompressed block length: {:?} / begins with {:?}", out.len(), out.get(0..8).unwrap());
+    pub fn from_reader_with_options<R: Read>(reader: R, options: &ParseOptions) -> Result<Replay, ReplayError> {
+        let mut decoder = ReplayDecoder::with_options(reader, options)?;
 
-                            data.append(&mut out);
-                        }
-                        Err(_) => {
-                            warn!("Failed to read datablock of length {:?}.", block_data_length);
+        let mut chat: Vec<ChatMessage> = vec![];
+        let mut actions: Vec<Action> = vec![];
+        let mut leaves: Vec<PlayerLeave> = vec![];
+        let mut startup_records: Vec<StartupRecord> = vec![];
+        let mut time_slots: Vec<TimeSlotLayout> = vec![];
+        let mut other_records: Vec<OtherRecord> = vec![];
+        let mut last_actions: HashMap<u8, u64> = HashMap::new();
+
+        while let Some(record) = decoder.next_record()? {
+            match record {
+                ReplayRecord::Startup(startup_record) => startup_records.push(startup_record),
+                ReplayRecord::LeaveGame(leave) => leaves.push(leave),
+                ReplayRecord::Chat(message) => chat.push(message),
+                ReplayRecord::Other(other_record) => other_records.push(other_record),
+                ReplayRecord::TimeSlot(time_slot) => {
+                    let mut layout = TimeSlotLayout { timestamp: time_slot.timestamp, increment: time_slot.increment, command_blocks: vec![] };
+                    for command_block in time_slot.command_blocks {
+                        last_actions.insert(command_block.player_id, time_slot.timestamp);
+                        let mut block_layout = CommandBlockLayout { player_id: command_block.player_id, actions: 0, trigger_chat: vec![] };
+                        for action in command_block.actions {
+                            let ActionKind::TriggerChat { unknown_a, unknown_b, message } = action.kind else {
+                                block_layout.actions += 1;
+                                actions.push(action);
+                                continue;
+                            };
+                            block_layout.trigger_chat.push((unknown_a, unknown_b));
+                            chat.push(ChatMessage {
+                                sender_player_id: action.player_id,
+                                channel: ChatChannel::Trigger,
+                                recipient_player_id: None,
+                                flag: None,
+                                message,
+                                timestamp: action.timestamp
+                            });
                         }
-                    };
-                    i += block_data_length + 12;
-                    k+=1;
+                        layout.command_blocks.push(block_layout);
+                    }
+                    time_slots.push(layout);
                 }
-                Err(_) => break
             }
         }
 
+        let game_start = decoder.game_start().unwrap_or(0);
+        let raw = decoder.take_raw();
+        let ReplaySummary { version, header, record_player_id, record_player_is_host: player_is_host, game_name, map_name, game_creator_battle_tag: game_creator_name, game_settings, game_start_info, slots, players: mut player_list, reforged_records } =
+            decoder.into_summary();
 
-        info!("Finished replay decoding. Total decoded data length: {:?}", data.len());
-        info!("Data starts with {:?}", data.get(0..128).unwrap());
-
-        // Decoding of the actual data
-
-        let mut cursor = Cursor::new(&data);
-
-
-        // 4.1 [PlayerRecord]
-        let player_is_host = cursor_read_byte(&mut cursor) == 0x00;
-        let player_id = cursor_read_byte(&mut cursor);
-
-        // Something new - undocumented
-        cursor_skip_bytes(&mut cursor, 4);
-
-        let player_name = cursor_read_nullterminated_string(&mut cursor);
-        info!("Player name: {:?}", player_name);
-
-        let additional_data_size_byte = cursor_read_byte(&mut cursor);
-        cursor_skip_bytes(&mut cursor, additional_data_size_byte as i64);
-
-
-        // 4.2 [GameName]
-        let game_name = cursor_read_nullterminated_string(&mut cursor);
-        info!("Game name: {:?}", game_name);
-
-        // There seems to be an additional NUL byte
-        cursor_skip_bytes(&mut cursor, 1);
-
-        // 4.3 [Encoded String]
-        let mut encoded_gamesettings_buf: Vec<u8> = vec![];
-        cursor.read_until(0x00, &mut encoded_gamesettings_buf).unwrap();
-
-        let game_settings_buf = decode_gamesettings(&encoded_gamesettings_buf);
-        info!("Decoded gamesettings: {:?}", game_settings_buf);
-
-        // 4.4 [GameSettings]
-        let game_speed = get_bits_value(game_settings_buf[0], [0, 1].as_ref());
-        let vis_hide_terrain = get_bits_value(game_settings_buf[1], [0].as_ref()) == 1;
-        let vis_map_explored = get_bits_value(game_settings_buf[1], [1].as_ref()) == 1;
-        let vis_always_visible = get_bits_value(game_settings_buf[1], [2].as_ref()) == 1;
-        let vis_default = get_bits_value(game_settings_buf[1], [3].as_ref()) == 1;
-        let obs_mode = get_bits_value(game_settings_buf[1], [4, 5].as_ref());
-        let teams_together = get_bits_value(game_settings_buf[1], [6].as_ref()) == 1;
-
-        let fixed_teams = get_bits_value(game_settings_buf[2], [1,2].as_ref());
-        let shared_unit_control = get_bits_value(game_settings_buf[3], [0].as_ref()) == 1;
-        let random_hero = get_bits_value(game_settings_buf[3], [1].as_ref()) == 1;
-        let random_races = get_bits_value(game_settings_buf[3], [2].as_ref()) == 1;
-        let obs_referees = get_bits_value(game_settings_buf[3], [6].as_ref()) == 1;
-
-        // 4.5 [Map&CreatorName]
-        let mut subcursor = Cursor::new(game_settings_buf[13..].as_ref());
-        let map_name = cursor_read_nullterminated_string(&mut subcursor);
-        let game_creator_name = cursor_read_nullterminated_string(&mut subcursor);
-
-        // 4.6 [PlayerCount]
-        let num_players_slots = cursor_read_dword(&mut cursor);
-
-        // 4.7 [GameType]
-        let game_type = cursor_read_byte(&mut cursor);
-        let is_private_custom_game = cursor_read_byte(&mut cursor);
-        cursor_skip_bytes(&mut cursor, 2);
-
-        // 4.8 [LanguageID?]
-        cursor_skip_bytes(&mut cursor, 4);
-
-        // 4.9 [PlayerList]
-        let mut player_list: HashMap<u8, ReplayPlayer> = HashMap::new();
-        player_list.insert(player_id,
-                           ReplayPlayer {
-                               battle_tag: player_name.clone(),
-                               leave_reason: LeaveReason::UNKNOWN,
-                               result_byte: 0,
-                               left_at: 0,
-                           }
-        );
-        let mut next_record_id = cursor_read_byte(&mut cursor);
-        while next_record_id == 0x00 || next_record_id == 0x16 {
-            let cur_player_id = cursor_read_byte(&mut cursor);
-            // cursor_skip_bytes(&mut cursor, 4);;
-            let cur_player_name = cursor_read_nullterminated_string(&mut cursor);
-            let additional_data_size_byte = cursor_read_byte(&mut cursor);
-            cursor_skip_bytes(&mut cursor, additional_data_size_byte as i64);
-            player_list.insert(cur_player_id, ReplayPlayer {
-                battle_tag: cur_player_name,
-                leave_reason: LeaveReason::UNKNOWN,
-                result_byte: 0,
-                left_at: 0,
-            });
-            next_record_id = cursor_read_byte(&mut cursor);
-        }
-        info!("Loaded player list: {:?}", player_list);
-
-        // Reforged player metadata
-        while next_record_id == 0x39 {
-            let cur_record_subtype = cursor_read_byte(&mut cursor);
-            let cur_record_data_length = cursor_read_dword(&mut cursor);
-
-            cursor_skip_bytes(&mut cursor, cur_record_data_length as i64);
-            // TODO: Maybe parse this data too
-
-            next_record_id = cursor_read_byte(&mut cursor);
+        for (player_id, left_at) in last_actions {
+            player_list.entry(player_id).and_modify(|x| x.left_at = left_at);
         }
 
-        // 4.10 [GameStartRecord]
-        if next_record_id != 0x19 {
-            let mut buf = [0u8; 128];
-            cursor.read_exact(&mut buf).unwrap();
-            panic!("GameStartRecord did not follow PlayerList: next record id = {:?}. Following bytes: {:?}", next_record_id, buf)
-        }
-
-        let data_length = cursor_read_word(&mut cursor);
-        let count_slotrecords = cursor_read_byte(&mut cursor);
-        let mut i = 0u8;
-
-        let mut slots: Vec<Slot> = Vec::with_capacity(count_slotrecords as usize);
-
-        while i < count_slotrecords {
-            let cur_slot_player_id = cursor_read_byte(&mut cursor);
-            let cur_slot_map_download_percent = cursor_read_byte(&mut cursor);
-            let status_byte = cursor_read_byte(&mut cursor);
-            let cur_slot_status = SlotStatus::from_u8(status_byte)
-                .or(Option::from(SlotStatus::UNKNOWN))
-                .unwrap();
-            let cur_slot_is_computer_player = cursor_read_byte(&mut cursor) == 1;
-            let cur_slot_team_index = cursor_read_byte(&mut cursor);
-            let color_byte = cursor_read_byte(&mut cursor);
-            let cur_slot_color =
-                SlotColor::from_u8(color_byte + 1)
-                    .or(Option::from(SlotColor::UNKNOWN))
-                    .unwrap();
-            let race_byte = cursor_read_byte(&mut cursor);
-            let cur_slot_player_race =
-                SlotRace::from_u8(race_byte)
-                    .or(Option::from(SlotRace::UNKNOWN))
-                    .unwrap();
-            let cur_slot_player_computer_ai_strenth =
-                ComputerAIStrength::from_u8(cursor_read_byte(&mut cursor))
-                    .or(Option::from(ComputerAIStrength::UNKNOWN))
-                    .unwrap();
-            let cur_slot_handicap_percent = cursor_read_byte(&mut cursor);
-
-            info!("Player slot record read: pid = {:?} status = {:?} is_comp = {:?} team = {:?} color = {:?} ({:?}) race = {:?} ({:?})",
-                cur_slot_player_id, cur_slot_status, cur_slot_is_computer_player, cur_slot_team_index, cur_slot_color, color_byte, cur_slot_player_race, race_byte);
-
-            slots.push(Slot {
-                player_id: cur_slot_player_id,
-                map_download_percent: cur_slot_map_download_percent,
-                status: cur_slot_status,
-                is_computer: cur_slot_is_computer_player,
-                team_index: cur_slot_team_index,
-                color: cur_slot_color,
-                race: cur_slot_player_race,
-                ai_strength: cur_slot_player_computer_ai_strenth,
-                handicap_percent: cur_slot_handicap_percent,
+        for leave in &leaves {
+            player_list.entry(leave.player_id).and_modify(|r| {
+                r.leave_reason = leave.reason;
+                r.result_byte = leave.result as u8;
             });
-
-            i+=1;
         }
 
-        let random_seed = cursor_read_dword(&mut cursor);
-        info!("Random seed: {:?}", random_seed);
-        let selection_mode = cursor_read_byte(&mut cursor);
-        info!("Selection mode: {:?}", selection_mode);
-        let start_spot_count = cursor_read_byte(&mut cursor);
-        info!("Start spots count: {:?}", start_spot_count);
+        let (saving_player_id, saving_player_method) = saver::identify_saving_player(&player_list, &leaves, record_player_id);
+        info!("Saving player: {:?} ({:?})", saving_player_id, saving_player_method);
 
-        // 5.0 [ReplayData]
-
-        // 0x17 LeaveGame
-        let from_index = cursor.position();
-        let mut next_record_id = cursor_read_byte(&mut cursor);
-        let mut chat: Vec<ChatMessage> = vec![];
-        let mut current_timestamp: u64 = 0;
-        let mut records: HashMap<u8, u64> = HashMap::new();
-        let mut action_records: HashMap<u8, u64> = HashMap::new();
-        let mut actions: Vec<Action> = vec![];
-        let mut last_leaver_index: u8 = 0;
-
-        loop {
-            // info!("Position {:?}, record {:?}", cursor.position() - 1, next_record_id);
-            match next_record_id {
-                0x17 => {
-                    let leave_reason_byte = cursor_read_dword(&mut cursor);
-                    let cur_leave_reason = LeaveReason::from_u32(leave_reason_byte).or(Option::from(LeaveReason::UNKNOWN)).unwrap();
-                    let cur_player_id = cursor_read_byte(&mut cursor);
-                    let cur_result = cursor_read_dword(&mut cursor);
-                    cursor_skip_bytes(&mut cursor, 4);
-
-                    info!("{:?} {:?}", cur_leave_reason, cur_result);
-                    player_list.entry(cur_player_id).and_modify(|r| {
-                        r.leave_reason = cur_leave_reason;
-                        r.result_byte = cur_result as u8;
-                    }
-                    );
-                    last_leaver_index = cur_player_id;
-                },
-                0x1A => {
-                    cursor_skip_bytes(&mut cursor, 4);
-                },
-                0x1B => {
-                    cursor_skip_bytes(&mut cursor, 4);
-                },
-                0x1C => {
-                    cursor_skip_bytes(&mut cursor, 4);
-                },
-                0x1E | 0x1F => {
-                    let mut len_following = cursor_read_word(&mut cursor);
-                    let increment = cursor_read_word(&mut cursor);
-                    // info!("Time increment: {:?}", increment);
-                    current_timestamp += increment as u64;
-                    len_following -= 2;
-                    let total_len_following = len_following.clone();
-                    let cursor_position_before_data_read = cursor.position();
-
-                    if len_following > 3 {
-                        loop {
-                            let cur_action_player_id = cursor_read_byte(&mut cursor);
-                            let cur_action_blocks_length = cursor_read_word(&mut cursor);
-                            len_following -= 3;
-
-                            player_list.entry(cur_action_player_id).and_modify(|x| x.left_at = current_timestamp);
-
-                            let position_before_read = cursor.position();
-                            let mut cur_read_bytes = 0;
-                            while cur_read_bytes < cur_action_blocks_length {
-                                let cur_position_before_read = cursor.position();
-
-                                let cur_action_id = cursor_read_byte(&mut cursor);
-                                if !action_records.contains_key(&cur_action_id)  {
-                                    action_records.insert(cur_action_id, 1);
-                                }
-                                else {
-                                    action_records.entry(cur_action_id).and_modify(|x| { *x += 1; });
-                                }
-
-                                let mut action = Action {
-                                    player_id: cur_action_player_id,
-                                    action_type: ActionType::from_u8(cur_action_id).or(Option::from(ActionType::UNKNOWN)).unwrap(),
-                                    timestamp: current_timestamp,
-                                    data: None,
-                                };
-
-                                match cur_action_id {
-                                    0x01 => {},
-                                    0x02 => {},
-                                    0x03 => {
-                                        let new_game_speed = cursor_read_byte(&mut cursor);
-                                    },
-                                    0x04 => {},
-                                    0x05 => {},
-                                    0x06 => {
-                                        let savegame_name = cursor_read_nullterminated_string(&mut cursor);
-                                        action.data = Option::from(ActionData {
-                                            savegame_name: Option::from(savegame_name),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x07 => {
-                                        cursor_skip_bytes(&mut cursor, 4);
-                                    },
-                                    0x10 => {
-                                        let flags = cursor_read_word(&mut cursor);
-
-                                        cursor_skip_bytes(&mut cursor, 2);
-                                        let item_id = cursor_read_ability_itemid(&mut cursor);
-
-                                        let unk_a = cursor_read_dword(&mut cursor);
-                                        let unk_b = cursor_read_dword(&mut cursor);
-
-                                        action.data = Option::from(ActionData {
-                                            item_id: Option::from(item_id.chars().rev().collect::<String>()),
-                                            ability_flags: Option::from(flags),
-                                            unknownA: Option::from(unk_a),
-                                            unknownB: Option::from(unk_b),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x11 => {
-                                        let flags = cursor_read_word(&mut cursor);
-
-                                        cursor_skip_bytes(&mut cursor, 2);
-                                        let item_id = cursor_read_ability_itemid(&mut cursor);
-
-                                        let unk_a = cursor_read_dword(&mut cursor);
-                                        let unk_b = cursor_read_dword(&mut cursor);
-
-                                        let loc_x = cursor_read_dword_float(&mut cursor);
-                                        let loc_y = cursor_read_dword_float(&mut cursor);
-
-                                        action.data = Option::from(ActionData {
-                                            item_id: Option::from(item_id.chars().rev().collect::<String>()),
-                                            ability_flags: Option::from(flags),
-                                            location: Option::from(MapLocation {
-                                                x: loc_x,
-                                                y: loc_y
-                                            }),
-                                            unknownA: Option::from(unk_a),
-                                            unknownB: Option::from(unk_b),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x12 => {
-                                        let flags = cursor_read_word(&mut cursor);
-
-                                        cursor_skip_bytes(&mut cursor, 2);
-                                        let item_id = cursor_read_ability_itemid(&mut cursor);
-
-                                        let unk_a = cursor_read_dword(&mut cursor);
-                                        let unk_b = cursor_read_dword(&mut cursor);
-
-                                        let loc_x = cursor_read_dword_float(&mut cursor);
-                                        let loc_y = cursor_read_dword_float(&mut cursor);
-
-                                        let obj_1 = cursor_read_dword(&mut cursor);
-                                        let obj_2 = cursor_read_dword(&mut cursor);
-
-                                        action.data = Option::from(ActionData {
-                                            item_id: Option::from(item_id.chars().rev().collect::<String>()),
-                                            ability_flags: Option::from(flags),
-                                            location: Option::from(MapLocation {
-                                                x: loc_x,
-                                                y: loc_y
-                                            }),
-                                            unknownA: Option::from(unk_a),
-                                            unknownB: Option::from(unk_b),
-                                            target_obj_id_1: Option::from(obj_1),
-                                            target_obj_id_2: Option::from(obj_2),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x13 => {
-                                        let flags = cursor_read_word(&mut cursor);
-
-                                        cursor_skip_bytes(&mut cursor, 2);
-                                        let item_id = cursor_read_ability_itemid(&mut cursor);
-
-                                        let unk_a = cursor_read_dword(&mut cursor);
-                                        let unk_b = cursor_read_dword(&mut cursor);
-
-                                        let loc_x = cursor_read_dword_float(&mut cursor);
-                                        let loc_y = cursor_read_dword_float(&mut cursor);
-
-                                        let obj_1 = cursor_read_dword(&mut cursor);
-                                        let obj_2 = cursor_read_dword(&mut cursor);
-
-                                        let item_obj_1 = cursor_read_dword(&mut cursor);
-                                        let item_obj_2 = cursor_read_dword(&mut cursor);
-
-                                        action.data = Option::from(ActionData {
-                                            item_id: Option::from(item_id.chars().rev().collect::<String>()),
-                                            ability_flags: Option::from(flags),
-                                            location: Option::from(MapLocation {
-                                                x: loc_x,
-                                                y: loc_y
-                                            }),
-                                            unknownA: Option::from(unk_a),
-                                            unknownB: Option::from(unk_b),
-                                            target_obj_id_1: Option::from(obj_1),
-                                            target_obj_id_2: Option::from(obj_2),
-                                            item_obj_id_1: Option::from(item_obj_1),
-                                            item_obj_id_2: Option::from(item_obj_2),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x14 => {
-                                        cursor_skip_bytes(&mut cursor, 43);
-                                    },
-                                    0x16 => {
-                                        let select_mode_byte = cursor_read_byte(&mut cursor);
-                                        let num_units = cursor_read_word(&mut cursor);
-                                        let mut ii: u16 = 0;
-                                        let mut objs: Vec<ObjectIDs> = vec![];
-                                        while ii < num_units {
-                                            objs.push(ObjectIDs {
-                                                id1: cursor_read_dword(&mut cursor),
-                                                id2: cursor_read_dword(&mut cursor),
-                                            });
-                                            ii += 1;
-                                        }
-                                        action.data = Option::from(ActionData {
-                                            sel_mode: SelectionMode::from_u8(select_mode_byte),
-                                            objects: Option::from(objs),
-                                            ..Default::default()
-                                        })
-                                        // cursor_skip_bytes(&mut cursor, 8*num_units as i64);
-                                    },
-                                    0x17 => {
-                                        let group_num = cursor_read_byte(&mut cursor);
-                                        let items_count = cursor_read_word(&mut cursor);
-                                        let mut ii: u16 = 0;
-                                        let mut objs: Vec<ObjectIDs> = vec![];
-                                        while ii < items_count {
-                                            objs.push(ObjectIDs {
-                                                id1: cursor_read_dword(&mut cursor),
-                                                id2: cursor_read_dword(&mut cursor),
-                                            });
-                                            ii += 1;
-                                        }
-                                        action.data = Option::from(ActionData {
-                                            group_id: Some(group_num),
-                                            objects: Option::from(objs),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x18 => {
-                                        cursor_skip_bytes(&mut cursor, 2);
-                                    },
-                                    0x19 => {
-                                        cursor_skip_bytes(&mut cursor, 12);
-                                    },
-                                    0x1A => {},
-                                    0x1B => {
-                                        cursor_skip_bytes(&mut cursor, 9);
-                                    },
-                                    0x1C => {
-                                        cursor_skip_bytes(&mut cursor, 9);
-                                    },
-                                    0x1D => {
-                                        cursor_skip_bytes(&mut cursor, 8);
-                                    },
-                                    0x1E => {
-                                        cursor_skip_bytes(&mut cursor, 5);
-                                    },
-                                    0x21 => {
-                                        cursor_skip_bytes(&mut cursor, 8);
-                                    },
-
-                                    0x20 => {},
-                                    0x22 => {},
-                                    0x23 => {},
-                                    0x24 => {},
-                                    0x25 => {},
-                                    0x26 => {},
-                                    0x27 => {
-                                        cursor_skip_bytes(&mut cursor, 5);
-                                    },
-                                    0x29 => {},
-                                    0x2A => {},
-                                    0x2B => {},
-                                    0x2C => {},
-                                    0x2D => {
-                                        cursor_skip_bytes(&mut cursor, 5);
-                                    },
-                                    0x2E => {
-                                        cursor_skip_bytes(&mut cursor, 4);
-                                    },
-                                    0x2F => {},
-                                    0x30 => {},
-                                    0x31 => {},
-                                    0x32 => {},
-
-                                    0x50 => {
-                                        cursor_skip_bytes(&mut cursor, 5);
-                                    },
-                                    0x51 => {
-                                        cursor_skip_bytes(&mut cursor, 9);
-                                    },
-
-                                    0x60 => {
-                                        let mut buf = vec![];
-                                        buf.resize(8, 0);
-                                        cursor.read_exact(&mut buf).unwrap();
-                                        let command = cursor_read_nullterminated_string(&mut cursor);
-                                        info!("Chat command (time {}) (player {}): {} {:?}", current_timestamp, cur_action_player_id, command, buf);
-
-                                        // W3C Replays: Chat messages stored here, but in other replays messages here might shadow chatmessages
-                                        if chat.iter().rfind(|el| el.sender_player_id == cur_action_player_id && el.message == command && el.timestamp.abs_diff(current_timestamp) < 500).is_none() {
-                                            chat.push(ChatMessage {
-                                                message: command,
-                                                timestamp: current_timestamp,
-                                                flag: None,
-                                                recipient_slot_number: None,
-                                                sender_player_id: cur_action_player_id
-                                            })
-                                        }
-                                    },
-                                    0x61 => {},
-                                    0x62 => {
-                                        action.data = Option::from(ActionData {
-                                            unknownA: Option::from(cursor_read_dword(&mut cursor)),
-                                            unknownB: Option::from(cursor_read_dword(&mut cursor)),
-                                            unknownC: Option::from(cursor_read_dword(&mut cursor)),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x66 => {},
-                                    0x67 => {},
-                                    0x68 => {
-                                        let x = cursor_read_dword_float(&mut cursor);
-                                        let y = cursor_read_dword_float(&mut cursor);
-                                        action.data = Option::from(ActionData {
-                                            location: Option::from(MapLocation {
-                                                x,
-                                                y
-                                            }),
-                                            ..Default::default()
-                                        })
-                                    },
-                                    0x69 => {
-                                        cursor_skip_bytes(&mut cursor, 16);
-                                    },
-                                    0x6A => {
-                                        cursor_skip_bytes(&mut cursor, 16);
-                                    },
-                                    0x75 => {
-                                        cursor_skip_bytes(&mut cursor, 1);
-                                    },
-
-                                    // Unknown
-                                    0x7a => {
-                                        cursor_skip_bytes(&mut cursor, 20);
-                                    },
-                                    0x7b => {
-                                        cursor_skip_bytes(&mut cursor, 16);
-                                    },
-
-                                    _ => {
-                                        let cur_pos = cursor.position().clone();
-                                        let left_bytes = cur_action_blocks_length as u64 + position_before_read - cur_pos;
-                                        warn!("({}) Unknown action id: {:#04x}. Read bytes so far: {:?}. Total expected: {:?}", cur_read_bytes, cur_action_id, cur_pos - position_before_read, cur_action_blocks_length);
-                                        let mut buf = vec![];
-                                        buf.resize(left_bytes as usize, 0);
-                                        cursor.read_exact(&mut buf).unwrap();
-                                        info!("Following bytes: {:?}", buf);
-                                        break;
-                                    }
-                                }
-
-                                if action.action_type != ActionType::UNKNOWN {
-                                    actions.push(action);
-                                }
-
-                                let cur_bytes = (cursor.position().clone() - cur_position_before_read) as u16;
-                                cur_read_bytes += cur_bytes;
-                            }
-
-                            len_following -= (cursor.position() - position_before_read) as u16;
-
-                            if len_following < 1 { break }
-                        }
-                    }
-
-                    if cursor.position() - cursor_position_before_data_read != total_len_following as u64 {
-                        warn!("Mismatch: {:?}/{:?}", cursor.position() - cursor_position_before_data_read, total_len_following);
-                    }
-                },
-                0x20 => {
-                    let cur_player_id = cursor_read_byte(&mut cursor);
-                    cursor_skip_bytes(&mut cursor, 2);
-                    let cur_flag = cursor_read_byte(&mut cursor);
-                    let cur_recepient_slotnumber: i8 = (cursor_read_dword(&mut cursor) as i32 - 2) as i8;
-                    let cur_message = cursor_read_nullterminated_string(&mut cursor);
-                    chat.push(ChatMessage {
-                        sender_player_id: cur_player_id,
-                        flag: Option::from(cur_flag),
-                        recipient_slot_number: Option::from(cur_recepient_slotnumber),
-                        message: cur_message,
-                        timestamp: current_timestamp
-                    })
-                },
-                0x22 => {
-                    cursor_skip_bytes(&mut cursor, 5);
-                },
-                0x23 => {
-                    cursor_skip_bytes(&mut cursor, 10);
-                },
-                0x2F => {
-                    cursor_skip_bytes(&mut cursor, 8);
-                },
-                0x00 => {
-                    info!("Exiting at null. Position: {:?}", cursor.position());
-                    break
-                }
-                _ => {
-                    info!("ReplayData: Unknown record id ({:#04x})", next_record_id);
-                    break
-                }
-            }
-            if !records.contains_key(&next_record_id) {
-                records.insert(next_record_id, 1);
-            }
-            else {
-                records.entry(next_record_id).and_modify(|x| { *x += 1; });
-            }
-            next_record_id = cursor_read_byte(&mut cursor);
-        }
-        info!("Records: {:?}", records);
-        info!("Action records: {:?}", action_records);
-
-        //
-        let mut saving_player_candidate_ids = player_list.keys().filter( |k| match player_list[k].leave_reason {
-            LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME => true,
-            _ => false
-        });
-
-        let saving_player_id: Option<&u8> =
-            if saving_player_candidate_ids.clone().count() == 1 { Option::from(saving_player_candidate_ids.next()) }
-            else { saving_player_candidate_ids.find(|k| player_list[k].battle_tag != "FLO") };
-
-        Replay {
-            version: *version,
+        Ok(Replay {
+            version,
+            header,
             metadata: ReplayMeta {
+                record_player_id,
+                record_player_is_host: player_is_host,
                 game_name,
-                is_saving_player_host: player_is_host,
-                saving_player_id: last_leaver_index,
+                is_saving_player_host: player_is_host && saving_player_id == record_player_id,
+                saving_player_id,
+                saving_player_method,
                 map_name,
                 game_creator_battle_tag: game_creator_name
             },
-            game_settings: GameSettings {
-                fixed_teams,
-                shared_unit_control,
-                random_hero,
-                random_races,
-                obs_referees,
-                vis_default,
-                vis_hide_terrain,
-                vis_always_visible,
-                vis_map_explored,
-                teams_together,
-                obs_mode,
-                game_speed
-            },
+            game_settings,
+            game_start_info,
             slots,
             players: player_list,
+            reforged_records,
             chat,
-            actions
-        }
+            actions,
+            leaves,
+            startup_records,
+            game_start,
+            raw,
+            time_slots,
+            other_records
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use serde::Serialize;
+use crate::{Action, ActionKind, ItemId};
+
+/// Game patch the bundled object data was taken from.
+pub const OBJECT_DATABASE_VERSION: &str = "1.36";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectCategory {
+    UNIT,
+    BUILDING,
+    UPGRADE,
+    HERO,
+    ITEM,
+    ABILITY,
+    UNKNOWN
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectRace {
+    HUMAN,
+    ORC,
+    NIGHTELF,
+    UNDEAD,
+    NEUTRAL
+}
+
+#[derive(Serialize, Debug)]
+pub struct ObjectInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub race: ObjectRace,
+    pub category: ObjectCategory
+}
+
+#[derive(Serialize, Debug)]
+pub struct OrderInfo {
+    pub id: u32,
+    pub name: &'static str
+}
+
+use ObjectCategory::*;
+use ObjectRace::*;
+
+const fn object(id: &'static str, name: &'static str, race: ObjectRace, category: ObjectCategory) -> ObjectInfo {
+    return ObjectInfo { id, name, race, category };
+}
+
+const fn order(id: u32, name: &'static str) -> OrderInfo {
+    return OrderInfo { id, name };
+}
+
+const OBJECTS: &[ObjectInfo] = &[
+    // Human
+    object("hpea", "Peasant", HUMAN, UNIT),
+    object("hfoo", "Footman", HUMAN, UNIT),
+    object("hkni", "Knight", HUMAN, UNIT),
+    object("hrif", "Rifleman", HUMAN, UNIT),
+    object("hmtm", "Mortar Team", HUMAN, UNIT),
+    object("hgyr", "Flying Machine", HUMAN, UNIT),
+    object("hgry", "Gryphon Rider", HUMAN, UNIT),
+    object("hmpr", "Priest", HUMAN, UNIT),
+    object("hsor", "Sorceress", HUMAN, UNIT),
+    object("hmtt", "Siege Engine", HUMAN, UNIT),
+    object("hspt", "Spell Breaker", HUMAN, UNIT),
+    object("hdhw", "Dragonhawk Rider", HUMAN, UNIT),
+    object("htow", "Town Hall", HUMAN, BUILDING),
+    object("hkee", "Keep", HUMAN, BUILDING),
+    object("hcas", "Castle", HUMAN, BUILDING),
+    object("hhou", "Farm", HUMAN, BUILDING),
+    object("hbar", "Barracks", HUMAN, BUILDING),
+    object("hbla", "Blacksmith", HUMAN, BUILDING),
+    object("hlum", "Lumber Mill", HUMAN, BUILDING),
+    object("hwtw", "Scout Tower", HUMAN, BUILDING),
+    object("hgtw", "Guard Tower", HUMAN, BUILDING),
+    object("hctw", "Cannon Tower", HUMAN, BUILDING),
+    object("hatw", "Arcane Tower", HUMAN, BUILDING),
+    object("harm", "Workshop", HUMAN, BUILDING),
+    object("hars", "Arcane Sanctum", HUMAN, BUILDING),
+    object("hgra", "Gryphon Aviary", HUMAN, BUILDING),
+    object("hvlt", "Arcane Vault", HUMAN, BUILDING),
+    object("halt", "Altar of Kings", HUMAN, BUILDING),
+    object("Hpal", "Paladin", HUMAN, HERO),
+    object("Hamg", "Archmage", HUMAN, HERO),
+    object("Hmkg", "Mountain King", HUMAN, HERO),
+    object("Hblm", "Blood Mage", HUMAN, HERO),
+    object("Rhme", "Melee Weapons", HUMAN, UPGRADE),
+    object("Rhra", "Ranged Weapons", HUMAN, UPGRADE),
+    object("Rhar", "Plating", HUMAN, UPGRADE),
+    object("Rhla", "Leather Armor", HUMAN, UPGRADE),
+    object("Rhac", "Masonry", HUMAN, UPGRADE),
+    object("Rhlh", "Lumber Harvesting", HUMAN, UPGRADE),
+    object("Rhde", "Defend", HUMAN, UPGRADE),
+    object("Rhan", "Animal War Training", HUMAN, UPGRADE),
+    object("Rhpt", "Priest Training", HUMAN, UPGRADE),
+    object("Rhst", "Sorceress Training", HUMAN, UPGRADE),
+    object("Rhri", "Long Rifles", HUMAN, UPGRADE),
+    object("Rhss", "Control Magic", HUMAN, UPGRADE),
+    object("Rhgb", "Flying Machine Bombs", HUMAN, UPGRADE),
+    object("Rhfl", "Flare", HUMAN, UPGRADE),
+    object("Rhfs", "Fragmentation Shards", HUMAN, UPGRADE),
+    object("Rhrt", "Barrage", HUMAN, UPGRADE),
+    object("Rhpm", "Backpack", HUMAN, UPGRADE),
+    object("Rhfc", "Flak Cannons", HUMAN, UPGRADE),
+    object("Rhcd", "Cloud", HUMAN, UPGRADE),
+    object("Rhhb", "Storm Hammers", HUMAN, UPGRADE),
+    object("Rhse", "Magic Sentry", HUMAN, UPGRADE),
+    object("Rhsb", "Sundering Blades", HUMAN, UPGRADE),
+    object("AHbz", "Blizzard", HUMAN, ABILITY),
+    object("AHwe", "Summon Water Elemental", HUMAN, ABILITY),
+    object("AHab", "Brilliance Aura", HUMAN, ABILITY),
+    object("AHmt", "Mass Teleport", HUMAN, ABILITY),
+    object("AHtb", "Storm Bolt", HUMAN, ABILITY),
+    object("AHtc", "Thunder Clap", HUMAN, ABILITY),
+    object("AHbh", "Bash", HUMAN, ABILITY),
+    object("AHav", "Avatar", HUMAN, ABILITY),
+    object("AHhb", "Holy Light", HUMAN, ABILITY),
+    object("AHds", "Divine Shield", HUMAN, ABILITY),
+    object("AHad", "Devotion Aura", HUMAN, ABILITY),
+    object("AHre", "Resurrection", HUMAN, ABILITY),
+    object("AHfs", "Flame Strike", HUMAN, ABILITY),
+    object("AHbn", "Banish", HUMAN, ABILITY),
+    object("AHdr", "Siphon Mana", HUMAN, ABILITY),
+    object("AHpx", "Summon Phoenix", HUMAN, ABILITY),
+
+    // Orc
+    object("opeo", "Peon", ORC, UNIT),
+    object("ogru", "Grunt", ORC, UNIT),
+    object("ohun", "Troll Headhunter", ORC, UNIT),
+    object("otbk", "Troll Berserker", ORC, UNIT),
+    object("ocat", "Demolisher", ORC, UNIT),
+    object("oshm", "Shaman", ORC, UNIT),
+    object("odoc", "Troll Witch Doctor", ORC, UNIT),
+    object("ospw", "Spirit Walker", ORC, UNIT),
+    object("orai", "Raider", ORC, UNIT),
+    object("okod", "Kodo Beast", ORC, UNIT),
+    object("owyv", "Wind Rider", ORC, UNIT),
+    object("otbr", "Troll Batrider", ORC, UNIT),
+    object("otau", "Tauren", ORC, UNIT),
+    object("ogre", "Great Hall", ORC, BUILDING),
+    object("ostr", "Stronghold", ORC, BUILDING),
+    object("ofrt", "Fortress", ORC, BUILDING),
+    object("otrb", "Orc Burrow", ORC, BUILDING),
+    object("obar", "Barracks", ORC, BUILDING),
+    object("ofor", "War Mill", ORC, BUILDING),
+    object("owtw", "Watch Tower", ORC, BUILDING),
+    object("oalt", "Altar of Storms", ORC, BUILDING),
+    object("obea", "Beastiary", ORC, BUILDING),
+    object("osld", "Spirit Lodge", ORC, BUILDING),
+    object("otto", "Tauren Totem", ORC, BUILDING),
+    object("ovln", "Voodoo Lounge", ORC, BUILDING),
+    object("Obla", "Blademaster", ORC, HERO),
+    object("Ofar", "Far Seer", ORC, HERO),
+    object("Otch", "Tauren Chieftain", ORC, HERO),
+    object("Oshd", "Shadow Hunter", ORC, HERO),
+    object("Rome", "Melee Weapons", ORC, UPGRADE),
+    object("Rora", "Ranged Weapons", ORC, UPGRADE),
+    object("Roar", "Unit Armor", ORC, UPGRADE),
+    object("Rwdm", "War Drums Damage Increase", ORC, UPGRADE),
+    object("Ropg", "Pillage", ORC, UPGRADE),
+    object("Robs", "Berserker Strength", ORC, UPGRADE),
+    object("Rows", "Pulverize", ORC, UPGRADE),
+    object("Roen", "Ensnare", ORC, UPGRADE),
+    object("Rovs", "Envenomed Spears", ORC, UPGRADE),
+    object("Rowd", "Witch Doctor Training", ORC, UPGRADE),
+    object("Rost", "Shaman Training", ORC, UPGRADE),
+    object("Rosp", "Spiked Barricades", ORC, UPGRADE),
+    object("Rotr", "Troll Regeneration", ORC, UPGRADE),
+    object("Rolf", "Liquid Fire", ORC, UPGRADE),
+    object("Ropm", "Backpack", ORC, UPGRADE),
+    object("Rowt", "Spirit Walker Training", ORC, UPGRADE),
+    object("Robk", "Berserker Upgrade", ORC, UPGRADE),
+    object("Rorb", "Reinforced Defenses", ORC, UPGRADE),
+    object("Robf", "Burning Oil", ORC, UPGRADE),
+    object("AOwk", "Wind Walk", ORC, ABILITY),
+    object("AOmi", "Mirror Image", ORC, ABILITY),
+    object("AOcr", "Critical Strike", ORC, ABILITY),
+    object("AOww", "Bladestorm", ORC, ABILITY),
+    object("AOcl", "Chain Lightning", ORC, ABILITY),
+    object("AOfs", "Far Sight", ORC, ABILITY),
+    object("AOsf", "Feral Spirit", ORC, ABILITY),
+    object("AOeq", "Earthquake", ORC, ABILITY),
+    object("AOsh", "Shockwave", ORC, ABILITY),
+    object("AOws", "War Stomp", ORC, ABILITY),
+    object("AOae", "Endurance Aura", ORC, ABILITY),
+    object("AOre", "Reincarnation", ORC, ABILITY),
+    object("AOhw", "Healing Wave", ORC, ABILITY),
+    object("AOhx", "Hex", ORC, ABILITY),
+    object("AOsw", "Serpent Ward", ORC, ABILITY),
+    object("AOvd", "Big Bad Voodoo", ORC, ABILITY),
+
+    // Night Elf
+    object("ewsp", "Wisp", NIGHTELF, UNIT),
+    object("earc", "Archer", NIGHTELF, UNIT),
+    object("esen", "Huntress", NIGHTELF, UNIT),
+    object("edry", "Dryad", NIGHTELF, UNIT),
+    object("ebal", "Glaive Thrower", NIGHTELF, UNIT),
+    object("ehip", "Hippogryph", NIGHTELF, UNIT),
+    object("echm", "Chimaera", NIGHTELF, UNIT),
+    object("edot", "Druid of the Talon", NIGHTELF, UNIT),
+    object("edoc", "Druid of the Claw", NIGHTELF, UNIT),
+    object("emtg", "Mountain Giant", NIGHTELF, UNIT),
+    object("efdr", "Faerie Dragon", NIGHTELF, UNIT),
+    object("etol", "Tree of Life", NIGHTELF, BUILDING),
+    object("etoa", "Tree of Ages", NIGHTELF, BUILDING),
+    object("etoe", "Tree of Eternity", NIGHTELF, BUILDING),
+    object("emow", "Moon Well", NIGHTELF, BUILDING),
+    object("eate", "Altar of Elders", NIGHTELF, BUILDING),
+    object("eaom", "Ancient of War", NIGHTELF, BUILDING),
+    object("eaoe", "Ancient of Lore", NIGHTELF, BUILDING),
+    object("eaow", "Ancient of Wind", NIGHTELF, BUILDING),
+    object("etrp", "Ancient Protector", NIGHTELF, BUILDING),
+    object("edob", "Hunter's Hall", NIGHTELF, BUILDING),
+    object("eden", "Ancient of Wonders", NIGHTELF, BUILDING),
+    object("edos", "Chimaera Roost", NIGHTELF, BUILDING),
+    object("Edem", "Demon Hunter", NIGHTELF, HERO),
+    object("Ekee", "Keeper of the Grove", NIGHTELF, HERO),
+    object("Emoo", "Priestess of the Moon", NIGHTELF, HERO),
+    object("Ewar", "Warden", NIGHTELF, HERO),
+    object("Resm", "Strength of the Moon", NIGHTELF, UPGRADE),
+    object("Resw", "Strength of the Wild", NIGHTELF, UPGRADE),
+    object("Rema", "Moon Armor", NIGHTELF, UPGRADE),
+    object("Rerh", "Reinforced Hides", NIGHTELF, UPGRADE),
+    object("Reuv", "Ultravision", NIGHTELF, UPGRADE),
+    object("Renb", "Nature's Blessing", NIGHTELF, UPGRADE),
+    object("Reib", "Improved Bows", NIGHTELF, UPGRADE),
+    object("Remk", "Marksmanship", NIGHTELF, UPGRADE),
+    object("Resc", "Sentinel", NIGHTELF, UPGRADE),
+    object("Remg", "Upgrade Moon Glaive", NIGHTELF, UPGRADE),
+    object("Redt", "Druid of the Talon Training", NIGHTELF, UPGRADE),
+    object("Redc", "Druid of the Claw Training", NIGHTELF, UPGRADE),
+    object("Resi", "Abolish Magic", NIGHTELF, UPGRADE),
+    object("Reht", "Hippogryph Taming", NIGHTELF, UPGRADE),
+    object("Recb", "Corrosive Breath", NIGHTELF, UPGRADE),
+    object("Repb", "Vorpal Blades", NIGHTELF, UPGRADE),
+    object("Rers", "Resistant Skin", NIGHTELF, UPGRADE),
+    object("Rehs", "Hardened Skin", NIGHTELF, UPGRADE),
+    object("Reeb", "Mark of the Claw", NIGHTELF, UPGRADE),
+    object("Reec", "Mark of the Talon", NIGHTELF, UPGRADE),
+    object("Rews", "Well Spring", NIGHTELF, UPGRADE),
+    object("Repm", "Backpack", NIGHTELF, UPGRADE),
+    object("AEmb", "Mana Burn", NIGHTELF, ABILITY),
+    object("AEim", "Immolation", NIGHTELF, ABILITY),
+    object("AEev", "Evasion", NIGHTELF, ABILITY),
+    object("AEme", "Metamorphosis", NIGHTELF, ABILITY),
+    object("AEer", "Entangling Roots", NIGHTELF, ABILITY),
+    object("AEfn", "Force of Nature", NIGHTELF, ABILITY),
+    object("AEah", "Thorns Aura", NIGHTELF, ABILITY),
+    object("AEtq", "Tranquility", NIGHTELF, ABILITY),
+    object("AEst", "Scout", NIGHTELF, ABILITY),
+    object("AHfa", "Searing Arrows", NIGHTELF, ABILITY),
+    object("AEar", "Trueshot Aura", NIGHTELF, ABILITY),
+    object("AEsf", "Starfall", NIGHTELF, ABILITY),
+    object("AEbl", "Blink", NIGHTELF, ABILITY),
+    object("AEfk", "Fan of Knives", NIGHTELF, ABILITY),
+    object("AEsh", "Shadow Strike", NIGHTELF, ABILITY),
+    object("AEsv", "Spirit of Vengeance", NIGHTELF, ABILITY),
+
+    // Undead
+    object("uaco", "Acolyte", UNDEAD, UNIT),
+    object("ugho", "Ghoul", UNDEAD, UNIT),
+    object("ucry", "Crypt Fiend", UNDEAD, UNIT),
+    object("ugar", "Gargoyle", UNDEAD, UNIT),
+    object("uabo", "Abomination", UNDEAD, UNIT),
+    object("umtw", "Meat Wagon", UNDEAD, UNIT),
+    object("unec", "Necromancer", UNDEAD, UNIT),
+    object("uban", "Banshee", UNDEAD, UNIT),
+    object("uobs", "Obsidian Statue", UNDEAD, UNIT),
+    object("ufro", "Frost Wyrm", UNDEAD, UNIT),
+    object("ushd", "Shade", UNDEAD, UNIT),
+    object("unpl", "Necropolis", UNDEAD, BUILDING),
+    object("unp1", "Halls of the Dead", UNDEAD, BUILDING),
+    object("unp2", "Black Citadel", UNDEAD, BUILDING),
+    object("uzig", "Ziggurat", UNDEAD, BUILDING),
+    object("uzg1", "Spirit Tower", UNDEAD, BUILDING),
+    object("uzg2", "Nerubian Tower", UNDEAD, BUILDING),
+    object("usep", "Crypt", UNDEAD, BUILDING),
+    object("ugrv", "Graveyard", UNDEAD, BUILDING),
+    object("uaod", "Altar of Darkness", UNDEAD, BUILDING),
+    object("utod", "Temple of the Damned", UNDEAD, BUILDING),
+    object("uslh", "Slaughterhouse", UNDEAD, BUILDING),
+    object("usap", "Sacrificial Pit", UNDEAD, BUILDING),
+    object("ubon", "Boneyard", UNDEAD, BUILDING),
+    object("utom", "Tomb of Relics", UNDEAD, BUILDING),
+    object("Udea", "Death Knight", UNDEAD, HERO),
+    object("Udre", "Dreadlord", UNDEAD, HERO),
+    object("Ulic", "Lich", UNDEAD, HERO),
+    object("Ucrl", "Crypt Lord", UNDEAD, HERO),
+    object("Rume", "Unholy Strength", UNDEAD, UPGRADE),
+    object("Rura", "Creature Attack", UNDEAD, UPGRADE),
+    object("Ruar", "Unholy Armor", UNDEAD, UPGRADE),
+    object("Rucr", "Creature Carapace", UNDEAD, UPGRADE),
+    object("Ruac", "Cannibalize", UNDEAD, UPGRADE),
+    object("Rugf", "Ghoul Frenzy", UNDEAD, UPGRADE),
+    object("Ruwb", "Web", UNDEAD, UPGRADE),
+    object("Rusf", "Stone Form", UNDEAD, UPGRADE),
+    object("Rune", "Necromancer Training", UNDEAD, UPGRADE),
+    object("Ruba", "Banshee Training", UNDEAD, UPGRADE),
+    object("Rufb", "Freezing Breath", UNDEAD, UPGRADE),
+    object("Rusl", "Skeletal Longevity", UNDEAD, UPGRADE),
+    object("Rupc", "Disease Cloud", UNDEAD, UPGRADE),
+    object("Rusm", "Skeletal Mastery", UNDEAD, UPGRADE),
+    object("Rubu", "Burrow", UNDEAD, UPGRADE),
+    object("Rusp", "Destroyer Form", UNDEAD, UPGRADE),
+    object("Ruex", "Exhume Corpses", UNDEAD, UPGRADE),
+    object("Rupm", "Backpack", UNDEAD, UPGRADE),
+    object("AUdc", "Death Coil", UNDEAD, ABILITY),
+    object("AUdp", "Death Pact", UNDEAD, ABILITY),
+    object("AUau", "Unholy Aura", UNDEAD, ABILITY),
+    object("AUan", "Animate Dead", UNDEAD, ABILITY),
+    object("AUsl", "Sleep", UNDEAD, ABILITY),
+    object("AUav", "Vampiric Aura", UNDEAD, ABILITY),
+    object("AUcs", "Carrion Swarm", UNDEAD, ABILITY),
+    object("AUin", "Inferno", UNDEAD, ABILITY),
+    object("AUfn", "Frost Nova", UNDEAD, ABILITY),
+    object("AUfu", "Frost Armor", UNDEAD, ABILITY),
+    object("AUdr", "Dark Ritual", UNDEAD, ABILITY),
+    object("AUdd", "Death and Decay", UNDEAD, ABILITY),
+    object("AUim", "Impale", UNDEAD, ABILITY),
+    object("AUts", "Spiked Carapace", UNDEAD, ABILITY),
+    object("AUcb", "Carrion Beetles", UNDEAD, ABILITY),
+    object("AUls", "Locust Swarm", UNDEAD, ABILITY),
+
+    // Neutral
+    object("ngsp", "Goblin Sapper", NEUTRAL, UNIT),
+    object("ngir", "Goblin Shredder", NEUTRAL, UNIT),
+    object("nzep", "Goblin Zeppelin", NEUTRAL, UNIT),
+    object("Nalc", "Goblin Alchemist", NEUTRAL, HERO),
+    object("Nngs", "Naga Sea Witch", NEUTRAL, HERO),
+    object("Ntin", "Goblin Tinker", NEUTRAL, HERO),
+    object("Nbst", "Beastmaster", NEUTRAL, HERO),
+    object("Npbm", "Pandaren Brewmaster", NEUTRAL, HERO),
+    object("Nbrn", "Dark Ranger", NEUTRAL, HERO),
+    object("Nfir", "Firelord", NEUTRAL, HERO),
+    object("Nplh", "Pit Lord", NEUTRAL, HERO),
+    object("ANbf", "Breath of Fire", NEUTRAL, ABILITY),
+    object("ANdb", "Drunken Brawler", NEUTRAL, ABILITY),
+    object("ANdh", "Drunken Haze", NEUTRAL, ABILITY),
+    object("ANef", "Storm, Earth, and Fire", NEUTRAL, ABILITY),
+    object("ANsi", "Silence", NEUTRAL, ABILITY),
+    object("ANba", "Black Arrow", NEUTRAL, ABILITY),
+    object("ANdr", "Life Drain", NEUTRAL, ABILITY),
+    object("ANch", "Charm", NEUTRAL, ABILITY),
+    object("ANfl", "Forked Lightning", NEUTRAL, ABILITY),
+    object("ANfa", "Frost Arrows", NEUTRAL, ABILITY),
+    object("ANms", "Mana Shield", NEUTRAL, ABILITY),
+    object("ANto", "Tornado", NEUTRAL, ABILITY),
+    object("ANsg", "Summon Bear", NEUTRAL, ABILITY),
+    object("ANsq", "Summon Quilbeast", NEUTRAL, ABILITY),
+    object("ANsw", "Summon Hawk", NEUTRAL, ABILITY),
+    object("ANst", "Stampede", NEUTRAL, ABILITY),
+    object("ANhs", "Healing Spray", NEUTRAL, ABILITY),
+    object("ANcr", "Chemical Rage", NEUTRAL, ABILITY),
+    object("ANab", "Acid Bomb", NEUTRAL, ABILITY),
+    object("ANtm", "Transmute", NEUTRAL, ABILITY),
+    object("ANsy", "Pocket Factory", NEUTRAL, ABILITY),
+    object("ANcs", "Cluster Rockets", NEUTRAL, ABILITY),
+    object("ANeg", "Engineering Upgrade", NEUTRAL, ABILITY),
+    object("ANrg", "Robo-Goblin", NEUTRAL, ABILITY),
+    object("ANso", "Soul Burn", NEUTRAL, ABILITY),
+    object("ANlm", "Summon Lava Spawn", NEUTRAL, ABILITY),
+    object("ANic", "Incinerate", NEUTRAL, ABILITY),
+    object("ANvc", "Volcano", NEUTRAL, ABILITY),
+    object("ANht", "Howl of Terror", NEUTRAL, ABILITY),
+    object("ANca", "Cleaving Attack", NEUTRAL, ABILITY),
+    object("ANrf", "Rain of Fire", NEUTRAL, ABILITY),
+    object("ANdo", "Doom", NEUTRAL, ABILITY),
+
+    // Items
+    object("stwp", "Scroll of Town Portal", NEUTRAL, ITEM),
+    object("phea", "Potion of Healing", NEUTRAL, ITEM),
+    object("pman", "Potion of Mana", NEUTRAL, ITEM),
+    object("plcl", "Lesser Clarity Potion", NEUTRAL, ITEM),
+    object("shea", "Scroll of Healing", NEUTRAL, ITEM),
+    object("dust", "Dust of Appearance", NEUTRAL, ITEM),
+    object("ankh", "Ankh of Reincarnation", NEUTRAL, ITEM),
+    object("bspd", "Boots of Speed", NEUTRAL, ITEM),
+    object("cnob", "Circlet of Nobility", NEUTRAL, ITEM),
+    object("prvt", "Periapt of Vitality", NEUTRAL, ITEM),
+    object("rde1", "Ring of Protection +2", NEUTRAL, ITEM),
+    object("rnec", "Rod of Necromancy", NEUTRAL, ITEM),
+    object("tsct", "Ivory Tower", NEUTRAL, ITEM),
+    object("sreg", "Scroll of Regeneration", NEUTRAL, ITEM),
+    object("spro", "Scroll of Protection", NEUTRAL, ITEM),
+    object("ssan", "Staff of Sanctuary", NEUTRAL, ITEM),
+    object("stel", "Staff of Teleportation", NEUTRAL, ITEM),
+    object("skul", "Sacrificial Skull", NEUTRAL, ITEM),
+    object("pghe", "Potion of Greater Healing", NEUTRAL, ITEM),
+    object("pgma", "Potion of Greater Mana", NEUTRAL, ITEM),
+    object("pnvu", "Potion of Invulnerability", NEUTRAL, ITEM),
+    object("pspd", "Potion of Speed", NEUTRAL, ITEM),
+    object("pinv", "Potion of Invisibility", NEUTRAL, ITEM),
+    object("mcri", "Mechanical Critter", NEUTRAL, ITEM),
+    object("moon", "Moonstone", NEUTRAL, ITEM),
+    object("oli2", "Orb of Lightning", NEUTRAL, ITEM),
+    object("shas", "Scroll of Speed", NEUTRAL, ITEM),
+    object("spre", "Staff of Preservation", NEUTRAL, ITEM),
+    object("ritd", "Ritual Dagger", NEUTRAL, ITEM),
+    object("hslv", "Healing Salve", NEUTRAL, ITEM),
+    object("ofro", "Orb of Frost", NEUTRAL, ITEM),
+    object("odef", "Orb of Darkness", NEUTRAL, ITEM),
+    object("tret", "Tome of Retraining", NEUTRAL, ITEM),
+    object("sneg", "Staff of Negation", NEUTRAL, ITEM),
+    object("wneg", "Wand of Negation", NEUTRAL, ITEM),
+    object("wneu", "Wand of Neutralization", NEUTRAL, ITEM),
+    object("wild", "Amulet of the Wild", NEUTRAL, ITEM),
+    object("fgsk", "Book of the Dead", NEUTRAL, ITEM),
+    object("fgrd", "Red Drake Egg", NEUTRAL, ITEM),
+    object("fgrg", "Stone Token", NEUTRAL, ITEM),
+    object("fgfh", "Spiked Collar", NEUTRAL, ITEM),
+    object("fgdg", "Demonic Figurine", NEUTRAL, ITEM),
+    object("wswd", "Sentry Wards", NEUTRAL, ITEM),
+    object("infs", "Inferno Stone", NEUTRAL, ITEM),
+    object("gsou", "Soul Gem", NEUTRAL, ITEM),
+    object("whwd", "Healing Wards", NEUTRAL, ITEM),
+    object("clfm", "Cloak of Flames", NEUTRAL, ITEM),
+    object("crys", "Crystal Ball", NEUTRAL, ITEM),
+    object("hcun", "Hood of Cunning", NEUTRAL, ITEM),
+    object("mcou", "Medallion of Courage", NEUTRAL, ITEM),
+    object("rlif", "Ring of Regeneration", NEUTRAL, ITEM),
+    object("evtl", "Talisman of Evasion", NEUTRAL, ITEM),
+    object("penr", "Pendant of Energy", NEUTRAL, ITEM),
+    object("rwiz", "Sobi Mask", NEUTRAL, ITEM),
+    object("ssil", "Staff of Silence", NEUTRAL, ITEM),
+    object("gemt", "Gem of True Seeing", NEUTRAL, ITEM)
+];
+
+// Order ids are sent instead of a FourCC for generic orders
+const ORDERS: &[OrderInfo] = &[
+    order(0x000D0003, "smart"),
+    order(0x000D0004, "stop"),
+    order(0x000D0008, "cancel"),
+    order(0x000D000C, "setrally"),
+    order(0x000D000D, "getitem"),
+    order(0x000D000F, "attack"),
+    order(0x000D0010, "attackground"),
+    order(0x000D0011, "attackonce"),
+    order(0x000D0012, "move"),
+    order(0x000D0014, "aimove"),
+    order(0x000D0016, "patrol"),
+    order(0x000D0019, "holdposition"),
+    order(0x000D001A, "build"),
+    order(0x000D001B, "humanbuild"),
+    order(0x000D001C, "orcbuild"),
+    order(0x000D001D, "nightelfbuild"),
+    order(0x000D001E, "undeadbuild"),
+    order(0x000D001F, "resumebuild"),
+    order(0x000D0021, "dropitem"),
+    order(0x000D0022, "moveslot1"),
+    order(0x000D0023, "moveslot2"),
+    order(0x000D0024, "moveslot3"),
+    order(0x000D0025, "moveslot4"),
+    order(0x000D0026, "moveslot5"),
+    order(0x000D0027, "moveslot6"),
+    order(0x000D0028, "useslot1"),
+    order(0x000D0029, "useslot2"),
+    order(0x000D002A, "useslot3"),
+    order(0x000D002B, "useslot4"),
+    order(0x000D002C, "useslot5"),
+    order(0x000D002D, "useslot6")
+];
+
+// Learnable skills of every melee and tavern hero, the ultimate last
+const HERO_ABILITIES: &[(&str, [&str; 4])] = &[
+    ("Hpal", ["AHhb", "AHds", "AHad", "AHre"]),
+    ("Hamg", ["AHbz", "AHwe", "AHab", "AHmt"]),
+    ("Hmkg", ["AHtb", "AHtc", "AHbh", "AHav"]),
+    ("Hblm", ["AHfs", "AHbn", "AHdr", "AHpx"]),
+    ("Obla", ["AOwk", "AOmi", "AOcr", "AOww"]),
+    ("Ofar", ["AOcl", "AOfs", "AOsf", "AOeq"]),
+    ("Otch", ["AOsh", "AOws", "AOae", "AOre"]),
+    ("Oshd", ["AOhw", "AOhx", "AOsw", "AOvd"]),
+    ("Edem", ["AEmb", "AEim", "AEev", "AEme"]),
+    ("Ekee", ["AEer", "AEfn", "AEah", "AEtq"]),
+    ("Emoo", ["AEst", "AHfa", "AEar", "AEsf"]),
+    ("Ewar", ["AEbl", "AEfk", "AEsh", "AEsv"]),
+    ("Udea", ["AUdc", "AUdp", "AUau", "AUan"]),
+    ("Udre", ["AUcs", "AUsl", "AUav", "AUin"]),
+    ("Ulic", ["AUfn", "AUfu", "AUdr", "AUdd"]),
+    ("Ucrl", ["AUim", "AUts", "AUcb", "AUls"]),
+    ("Nalc", ["ANhs", "ANcr", "ANab", "ANtm"]),
+    ("Nngs", ["ANfl", "ANfa", "ANms", "ANto"]),
+    ("Ntin", ["ANsy", "ANcs", "ANeg", "ANrg"]),
+    ("Nbst", ["ANsg", "ANsq", "ANsw", "ANst"]),
+    ("Npbm", ["ANbf", "ANdb", "ANdh", "ANef"]),
+    ("Nbrn", ["ANsi", "ANba", "ANdr", "ANch"]),
+    ("Nfir", ["ANso", "ANlm", "ANic", "ANvc"]),
+    ("Nplh", ["ANrf", "ANht", "ANca", "ANdo"])
+];
+
+fn objects() -> &'static HashMap<&'static str, &'static ObjectInfo> {
+    static OBJECTS_BY_ID: OnceLock<HashMap<&'static str, &'static ObjectInfo>> = OnceLock::new();
+    return OBJECTS_BY_ID.get_or_init(|| OBJECTS.iter().map(|o| (o.id, o)).collect());
+}
+
+pub fn object_info(fourcc: &str) -> Option<&'static ObjectInfo> {
+    return objects().get(fourcc).copied();
+}
+
+pub fn order_info(order_id: u32) -> Option<&'static OrderInfo> {
+    return ORDERS.iter().find(|o| o.id == order_id);
+}
+
+pub fn hero_abilities(hero: &str) -> Option<&'static [&'static str; 4]> {
+    return HERO_ABILITIES.iter().find(|(id, _)| *id == hero).map(|(_, abilities)| abilities);
+}
+
+/// Hero which learns the given skill.
+pub fn ability_hero(ability: &str) -> Option<&'static str> {
+    return HERO_ABILITIES.iter().find(|(_, abilities)| abilities.contains(&ability)).map(|(id, _)| *id);
+}
+
+/// Classifies a FourCC as unit, building, upgrade, hero, item or hero ability.
+pub fn object_category(fourcc: &str) -> ObjectCategory {
+    if let Some(info) = object_info(fourcc) {
+        return info.category;
+    }
+    // Upgrades share the R prefix, e.g. Rhme
+    if fourcc.len() == 4 && fourcc.starts_with('R') {
+        return ObjectCategory::UPGRADE;
+    }
+    return ObjectCategory::UNKNOWN;
+}
+
+impl ItemId {
+    pub fn category(&self) -> ObjectCategory {
+        return match self {
+            ItemId::FourCC(fourcc) => object_category(fourcc),
+            ItemId::Order(_) => ObjectCategory::UNKNOWN
+        };
+    }
+
+    pub fn object_info(&self) -> Option<&'static ObjectInfo> {
+        return match self {
+            ItemId::FourCC(fourcc) => object_info(fourcc),
+            ItemId::Order(_) => None
+        };
+    }
+
+    pub fn order_info(&self) -> Option<&'static OrderInfo> {
+        return match self {
+            ItemId::FourCC(_) => None,
+            ItemId::Order(order_id) => order_info(*order_id)
+        };
+    }
+
+    /// Name of the object or order, falling back to the raw id.
+    pub fn name(&self) -> String {
+        if let Some(info) = self.object_info() {
+            return info.name.to_string();
+        }
+        if let Some(info) = self.order_info() {
+            return info.name.to_string();
+        }
+        return match self {
+            ItemId::FourCC(fourcc) => fourcc.clone(),
+            ItemId::Order(order_id) => format!("{:#010x}", order_id)
+        };
+    }
+}
+
+impl ActionKind {
+    /// Item id of ability, item transfer, subgroup and queue actions.
+    pub fn item_id(&self) -> Option<&ItemId> {
+        return match self {
+            ActionKind::AbilityBasic { item_id, .. } |
+            ActionKind::AbilityWithTargetLocation { item_id, .. } |
+            ActionKind::AbilityWithTargetObject { item_id, .. } |
+            ActionKind::ItemTransfer { item_id, .. } |
+            ActionKind::AbilityWithTwoTargetLocations { item_id, .. } |
+            ActionKind::SelectSubgroup { item_id, .. } |
+            ActionKind::RemoveFromQueue { item_id, .. } => Some(item_id),
+            _ => None
+        };
+    }
+}
+
+impl Action {
+    pub fn item_id(&self) -> Option<&ItemId> {
+        return self.kind.item_id();
+    }
+
+    pub fn object_info(&self) -> Option<&'static ObjectInfo> {
+        return self.item_id().and_then(|item_id| item_id.object_info());
+    }
+
+    pub fn order_info(&self) -> Option<&'static OrderInfo> {
+        return self.item_id().and_then(|item_id| item_id.order_info());
+    }
+}
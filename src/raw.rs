@@ -0,0 +1,37 @@
+use serde::Serialize;
+
+/// Original bytes of a ReplayData record, kept with [`crate::ParseOptions::keep_raw_records`].
+#[derive(Serialize, Debug, Clone)]
+pub struct RawRecord {
+    pub id: u8,
+    /// Offset in the decompressed data
+    pub offset: u64,
+    /// Game time after the record was read
+    pub timestamp: u64,
+    /// The complete record including its id
+    pub data: Vec<u8>
+}
+
+/// The decompressed data of a replay as read, kept with [`crate::ParseOptions::keep_raw_records`].
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RawReplay {
+    /// Header and SubHeader (2.0 and 2.1)
+    pub header: Vec<u8>,
+    /// 4.0 - 4.10, everything in front of the ReplayData
+    pub game_info: Vec<u8>,
+    pub records: Vec<RawRecord>,
+    /// Data from the end of the ReplayData on, i.e. the terminating 0x00 and the padding of the last block
+    pub trailing_data: Vec<u8>
+}
+
+impl RawReplay {
+    /// The complete decompressed data.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = self.game_info.clone();
+        for record in &self.records {
+            data.extend_from_slice(&record.data);
+        }
+        data.extend_from_slice(&self.trailing_data);
+        return data;
+    }
+}
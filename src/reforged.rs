@@ -0,0 +1,201 @@
+use serde::Serialize;
+
+// Subtype of the 0x39 records holding a player profile
+pub(crate) const PLAYER_PROFILE_SUBTYPE: u8 = 0x03;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum ProtobufValue {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Fixed32(u32)
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProtobufField {
+    pub number: u32,
+    pub value: ProtobufValue
+}
+
+impl ProtobufField {
+    fn as_u32(&self) -> Option<u32> {
+        return match self.value {
+            ProtobufValue::Varint(v) => u32::try_from(v).ok(),
+            _ => None
+        };
+    }
+
+    fn as_string(&self) -> Option<String> {
+        return match &self.value {
+            ProtobufValue::Bytes(bytes) => String::from_utf8(bytes.clone()).ok(),
+            _ => None
+        };
+    }
+}
+
+/// A protobuf encoded 0x39 record.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReforgedRecord {
+    pub subtype: u8,
+    pub fields: Vec<ProtobufField>,
+    /// Data of a record which is no valid protobuf message, `fields` is empty then
+    pub raw_data: Option<Vec<u8>>
+}
+
+impl ReforgedRecord {
+    /// Value of field 1, which holds the player id in all known subtypes.
+    pub fn player_id(&self) -> Option<u32> {
+        return self.fields.iter().find(|f| f.number == 1).and_then(|f| f.as_u32());
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ReforgedPlayerMetadata {
+    pub player_id: u32,
+    pub battle_tag: String,
+    pub clan: String,
+    pub portrait: String,
+    /// Fields of the player profile which are not decoded
+    pub unknown_fields: Vec<ProtobufField>,
+    /// Further 0x39 records of this player, e.g. skins
+    pub other_records: Vec<ReforgedRecord>
+}
+
+impl ReforgedPlayerMetadata {
+    pub(crate) fn from_profile(record: ReforgedRecord) -> ReforgedPlayerMetadata {
+        let mut metadata = ReforgedPlayerMetadata::default();
+        for field in record.fields {
+            match (field.number, field.as_u32(), field.as_string()) {
+                (1, Some(player_id), _) => metadata.player_id = player_id,
+                (2, _, Some(battle_tag)) => metadata.battle_tag = battle_tag,
+                (3, _, Some(clan)) => metadata.clan = clan,
+                (4, _, Some(portrait)) => metadata.portrait = portrait,
+                _ => metadata.unknown_fields.push(field)
+            }
+        }
+        return metadata;
+    }
+
+    // Inverse of from_profile, the decoded fields come first
+    pub(crate) fn to_profile(&self) -> ReforgedRecord {
+        let mut fields = vec![
+            ProtobufField { number: 1, value: ProtobufValue::Varint(self.player_id as u64) },
+            ProtobufField { number: 2, value: ProtobufValue::Bytes(self.battle_tag.as_bytes().to_vec()) },
+            ProtobufField { number: 3, value: ProtobufValue::Bytes(self.clan.as_bytes().to_vec()) },
+            ProtobufField { number: 4, value: ProtobufValue::Bytes(self.portrait.as_bytes().to_vec()) }
+        ];
+        fields.extend(self.unknown_fields.iter().cloned());
+        return ReforgedRecord { subtype: PLAYER_PROFILE_SUBTYPE, fields, raw_data: None };
+    }
+
+    // Metadata created for records other than the profile has no profile fields
+    pub(crate) fn has_profile(&self) -> bool {
+        return !self.battle_tag.is_empty() || !self.clan.is_empty() || !self.portrait.is_empty() || !self.unknown_fields.is_empty();
+    }
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> Option<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*position)?;
+        *position += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    return None;
+}
+
+fn read_slice<'a>(data: &'a [u8], position: &mut usize, length: usize) -> Option<&'a [u8]> {
+    let slice = data.get(*position..position.checked_add(length)?)?;
+    *position += length;
+    return Some(slice);
+}
+
+// Decodes the fields of a protobuf message without knowing its schema
+pub(crate) fn decode_protobuf(data: &[u8]) -> Option<Vec<ProtobufField>> {
+    let mut fields: Vec<ProtobufField> = vec![];
+    let mut position = 0usize;
+    while position < data.len() {
+        let key = read_varint(data, &mut position)?;
+        let number = u32::try_from(key >> 3).ok()?;
+        let value = match key & 0x07 {
+            0 => ProtobufValue::Varint(read_varint(data, &mut position)?),
+            1 => ProtobufValue::Fixed64(u64::from_le_bytes(read_slice(data, &mut position, 8)?.try_into().ok()?)),
+            2 => {
+                let length = usize::try_from(read_varint(data, &mut position)?).ok()?;
+                ProtobufValue::Bytes(read_slice(data, &mut position, length)?.to_vec())
+            },
+            5 => ProtobufValue::Fixed32(u32::from_le_bytes(read_slice(data, &mut position, 4)?.try_into().ok()?)),
+            _ => return None
+        };
+        fields.push(ProtobufField { number, value });
+    }
+    return Some(fields);
+}
+
+impl ReforgedRecord {
+    // Record data as written to the replay
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        return match &self.raw_data {
+            Some(data) => data.clone(),
+            None => encode_protobuf(&self.fields)
+        };
+    }
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+// Inverse of decode_protobuf
+pub(crate) fn encode_protobuf(fields: &[ProtobufField]) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    for field in fields {
+        let number = (field.number as u64) << 3;
+        match &field.value {
+            ProtobufValue::Varint(v) => {
+                write_varint(&mut data, number);
+                write_varint(&mut data, *v);
+            },
+            ProtobufValue::Fixed64(v) => {
+                write_varint(&mut data, number | 1);
+                data.extend_from_slice(&v.to_le_bytes());
+            },
+            ProtobufValue::Bytes(bytes) => {
+                write_varint(&mut data, number | 2);
+                write_varint(&mut data, bytes.len() as u64);
+                data.extend_from_slice(bytes);
+            },
+            ProtobufValue::Fixed32(v) => {
+                write_varint(&mut data, number | 5);
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+    return data;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protobuf_encoding_is_inverse() {
+        let fields = vec![
+            ProtobufField { number: 1, value: ProtobufValue::Varint(300) },
+            ProtobufField { number: 2, value: ProtobufValue::Bytes(b"Alice#123".to_vec()) },
+            ProtobufField { number: 3, value: ProtobufValue::Fixed64(u64::MAX) },
+            ProtobufField { number: 4, value: ProtobufValue::Fixed32(0xDEADBEEF) },
+            ProtobufField { number: 1000, value: ProtobufValue::Varint(0) }
+        ];
+        let encoded = encode_protobuf(&fields);
+        assert_eq!(decode_protobuf(&encoded), Some(fields));
+        assert_eq!(encode_protobuf(&decode_protobuf(&encoded).unwrap()), encoded);
+    }
+}
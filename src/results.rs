@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{LeaveReason, LeaveResult, Replay, Slot, SlotColor, SlotStatus};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameOutcome {
+    WIN,
+    LOSS,
+    DRAW,
+    UNKNOWN
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResultConfidence {
+    /// Guessed from the order in which players left
+    LOW,
+    /// Derived from the results of other players or the saving player leaving last
+    MEDIUM,
+    /// Stated by the result code of the LeaveGame record
+    HIGH
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerResult {
+    pub player_id: u8,
+    pub team: u8,
+    pub outcome: GameOutcome,
+    pub confidence: ResultConfidence
+}
+
+#[derive(Serialize, Debug)]
+pub struct TeamResult {
+    pub team: u8,
+    pub player_ids: Vec<u8>,
+    pub outcome: GameOutcome,
+    pub confidence: ResultConfidence
+}
+
+#[derive(Serialize, Debug)]
+pub struct MatchResult {
+    pub players: Vec<PlayerResult>,
+    pub teams: Vec<TeamResult>,
+    /// Lowest confidence of all teams
+    pub confidence: ResultConfidence
+}
+
+// Observers are in the team following the last one, 12 or 24 depending on the number of slots
+fn is_observer(slot: &Slot, slot_count: usize) -> bool {
+    let observer_team = if slot_count > 12 { 24 } else { 12 };
+    return slot.team_index == observer_team || matches!(slot.color, SlotColor::OBSERVER);
+}
+
+impl Replay {
+    /// Determines win, loss or draw of every player and team.
+    ///
+    /// Result codes of the LeaveGame records are used where they are conclusive. Otherwise the team
+    /// which stayed in the game the longest is assumed to have won, as leaving concedes a melee game.
+    /// Computer players and observers are not included.
+    pub fn match_result(&self) -> MatchResult {
+        // Leave position of every player, players without LeaveGame record stayed until the end
+        let leave_order: HashMap<u8, usize> = self.leaves.iter().enumerate().map(|(i, l)| (l.player_id, i)).collect();
+        let saving_player_id = self.metadata.saving_player_id;
+
+        let mut teams: Vec<TeamResult> = vec![];
+        let mut players: Vec<PlayerResult> = vec![];
+        for slot in &self.slots {
+            if !matches!(slot.status, SlotStatus::OCCUPIED) || slot.is_computer || is_observer(slot, self.slots.len()) {
+                continue;
+            }
+            let Some(player) = self.players.get(&slot.player_id) else {
+                continue;
+            };
+            let (outcome, confidence) = match player.leave_result() {
+                LeaveResult::WON => (GameOutcome::WIN, ResultConfidence::HIGH),
+                LeaveResult::LOST => (GameOutcome::LOSS, ResultConfidence::HIGH),
+                LeaveResult::DRAW => (GameOutcome::DRAW, ResultConfidence::HIGH),
+                _ => (GameOutcome::UNKNOWN, ResultConfidence::LOW)
+            };
+            players.push(PlayerResult { player_id: slot.player_id, team: slot.team_index, outcome, confidence });
+            match teams.iter_mut().find(|t| t.team == slot.team_index) {
+                Some(team) => team.player_ids.push(slot.player_id),
+                None => teams.push(TeamResult {
+                    team: slot.team_index,
+                    player_ids: vec![slot.player_id],
+                    outcome: GameOutcome::UNKNOWN,
+                    confidence: ResultConfidence::LOW
+                })
+            }
+        }
+
+        let team_has = |team: &TeamResult, outcome: GameOutcome| players.iter()
+            .any(|p| p.team == team.team && p.outcome == outcome && p.confidence == ResultConfidence::HIGH);
+        let winners: Vec<u8> = teams.iter().filter(|t| team_has(t, GameOutcome::WIN)).map(|t| t.team).collect();
+        let draw = teams.iter().any(|t| team_has(t, GameOutcome::DRAW));
+        let explicit_losers: Vec<u8> = teams.iter().filter(|t| team_has(t, GameOutcome::LOSS)).map(|t| t.team).collect();
+
+        if !winners.is_empty() {
+            for team in teams.iter_mut() {
+                (team.outcome, team.confidence) = if winners.contains(&team.team) {
+                    (GameOutcome::WIN, ResultConfidence::HIGH)
+                }
+                else if explicit_losers.contains(&team.team) {
+                    (GameOutcome::LOSS, ResultConfidence::HIGH)
+                }
+                else {
+                    (GameOutcome::LOSS, ResultConfidence::MEDIUM)
+                };
+            }
+        }
+        else if draw {
+            for team in teams.iter_mut() {
+                (team.outcome, team.confidence) = (GameOutcome::DRAW, ResultConfidence::HIGH);
+            }
+        }
+        else {
+            // First and last leave position of every team
+            let leave_span = |team: &TeamResult| {
+                let positions = team.player_ids.iter().map(|id| leave_order.get(id).copied().unwrap_or(usize::MAX));
+                return (positions.clone().min().unwrap_or(usize::MAX), positions.max().unwrap_or(usize::MAX));
+            };
+            let spans: Vec<(usize, usize)> = teams.iter().map(leave_span).collect();
+            let last = spans.iter().map(|s| s.1).max();
+            let candidates: Vec<usize> = (0..teams.len()).filter(|i| Some(spans[*i].1) == last).collect();
+
+            if let [winner] = candidates[..] {
+                // All opponents were gone before the first player of the winning team left
+                let clear = (0..teams.len()).all(|i| i == winner || spans[i].1 < spans[winner].0);
+                // The saver leaves last in every replay, which only tells something if it is conclusive
+                let saver_won = teams[winner].player_ids.contains(&saving_player_id)
+                    && self.players.get(&saving_player_id).is_some_and(|p| p.leave_reason == LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME);
+                let confidence = if clear && (saver_won || teams.len() == 2) { ResultConfidence::MEDIUM } else { ResultConfidence::LOW };
+                for (i, team) in teams.iter_mut().enumerate() {
+                    team.outcome = if i == winner { GameOutcome::WIN } else { GameOutcome::LOSS };
+                    team.confidence = confidence;
+                }
+            }
+        }
+
+        for player in players.iter_mut() {
+            if player.confidence == ResultConfidence::HIGH {
+                continue;
+            }
+            if let Some(team) = teams.iter().find(|t| t.team == player.team) {
+                player.outcome = team.outcome;
+                player.confidence = team.confidence;
+            }
+        }
+
+        let confidence = teams.iter().map(|t| t.confidence).min().unwrap_or(ResultConfidence::LOW);
+        return MatchResult { players, teams, confidence };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReplaySummary;
+    use crate::testing::{leave, replay, summary};
+
+    const REMOTE: LeaveReason = LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME;
+    const LOCAL: LeaveReason = LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME;
+
+    fn outcomes(result: &MatchResult) -> Vec<(u8, GameOutcome, ResultConfidence)> {
+        return result.teams.iter().map(|t| (t.team, t.outcome, t.confidence)).collect();
+    }
+
+    fn four_players() -> ReplaySummary {
+        return summary(&["Alice#1", "Bob#2", "Carol#3", "Dave#4"]);
+    }
+
+    #[test]
+    fn explicit_result() {
+        let replay = replay(&four_players(), &[
+            leave(2, REMOTE, LeaveResult::LOST),
+            leave(3, REMOTE, LeaveResult::WON),
+            leave(4, REMOTE, LeaveResult::LEFT),
+            leave(1, LOCAL, LeaveResult::LEFT)
+        ]);
+        let result = replay.match_result();
+        assert_eq!(outcomes(&result), [(0, GameOutcome::WIN, ResultConfidence::HIGH), (1, GameOutcome::LOSS, ResultConfidence::HIGH)]);
+        // Players without a conclusive result code get the result of their team
+        let players: Vec<(u8, GameOutcome, ResultConfidence)> = result.players.iter().map(|p| (p.player_id, p.outcome, p.confidence)).collect();
+        assert_eq!(players, [
+            (1, GameOutcome::WIN, ResultConfidence::HIGH),
+            (2, GameOutcome::LOSS, ResultConfidence::HIGH),
+            (3, GameOutcome::WIN, ResultConfidence::HIGH),
+            (4, GameOutcome::LOSS, ResultConfidence::HIGH)
+        ]);
+        assert_eq!(result.confidence, ResultConfidence::HIGH);
+    }
+
+    #[test]
+    fn opponents_of_the_winner_lose() {
+        let replay = replay(&four_players(), &[
+            leave(2, REMOTE, LeaveResult::LEFT),
+            leave(1, LOCAL, LeaveResult::WON)
+        ]);
+        assert_eq!(outcomes(&replay.match_result()), [(0, GameOutcome::WIN, ResultConfidence::HIGH), (1, GameOutcome::LOSS, ResultConfidence::MEDIUM)]);
+    }
+
+    #[test]
+    fn two_teams_by_leave_order() {
+        let replay = replay(&four_players(), &[
+            leave(2, REMOTE, LeaveResult::LEFT),
+            leave(4, REMOTE, LeaveResult::LEFT),
+            leave(3, REMOTE, LeaveResult::LEFT),
+            leave(1, LOCAL, LeaveResult::LEFT)
+        ]);
+        let result = replay.match_result();
+        assert_eq!(outcomes(&result), [(0, GameOutcome::WIN, ResultConfidence::MEDIUM), (1, GameOutcome::LOSS, ResultConfidence::MEDIUM)]);
+        assert_eq!(result.confidence, ResultConfidence::MEDIUM);
+    }
+
+    #[test]
+    fn interleaved_leaves_are_guessed() {
+        let replay = replay(&four_players(), &[
+            leave(2, REMOTE, LeaveResult::LEFT),
+            leave(3, REMOTE, LeaveResult::LEFT),
+            leave(4, REMOTE, LeaveResult::LEFT),
+            leave(1, LOCAL, LeaveResult::LEFT)
+        ]);
+        assert_eq!(outcomes(&replay.match_result()), [(0, GameOutcome::WIN, ResultConfidence::LOW), (1, GameOutcome::LOSS, ResultConfidence::LOW)]);
+    }
+
+    #[test]
+    fn ambiguous_free_for_all() {
+        let mut summary = summary(&["Alice#1", "Bob#2", "Carol#3"]);
+        summary.slots[2].team_index = 2;
+        // The last player to leave did not save the replay
+        let replay = replay(&summary, &[
+            leave(1, LOCAL, LeaveResult::LEFT),
+            leave(2, REMOTE, LeaveResult::LEFT),
+            leave(3, REMOTE, LeaveResult::LEFT)
+        ]);
+        let result = replay.match_result();
+        assert_eq!(outcomes(&result), [
+            (0, GameOutcome::LOSS, ResultConfidence::LOW),
+            (1, GameOutcome::LOSS, ResultConfidence::LOW),
+            (2, GameOutcome::WIN, ResultConfidence::LOW)
+        ]);
+        assert_eq!(result.confidence, ResultConfidence::LOW);
+    }
+
+    #[test]
+    fn observer_team_depends_on_slot_count() {
+        let mut summary = summary(&["Alice#1", "Bob#2", "Carol#3"]);
+        summary.slots[2].team_index = 12;
+        let players = |summary: &ReplaySummary| -> Vec<u8> {
+            replay(summary, &[]).match_result().players.iter().map(|p| p.player_id).collect()
+        };
+        assert_eq!(players(&summary), [1, 2]);
+
+        // With more than 12 slots team 12 plays and observers are on team 24
+        let mut empty_slot = summary.slots[0].clone();
+        (empty_slot.player_id, empty_slot.status) = (0, SlotStatus::EMPTY);
+        summary.slots.resize(24, empty_slot);
+        assert_eq!(players(&summary), [1, 2, 3]);
+        summary.slots[2].team_index = 24;
+        assert_eq!(players(&summary), [1, 2]);
+    }
+}
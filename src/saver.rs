@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{LeaveReason, PlayerLeave, ReplayPlayer};
+
+/// Names of host bots which join the game as a player.
+pub const HOST_BOT_NAMES: &[&str] = &["FLO"];
+
+/// How `ReplayMeta::saving_player_id` was determined, most reliable first.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum SavingPlayerMethod {
+    /// The only player whose connection was closed by the local game
+    LOCAL_LEAVE_REASON,
+    /// The 4.1 PlayerRecord, if it is no host bot
+    PLAYER_RECORD,
+    /// The player who left last
+    LAST_LEAVER,
+    UNKNOWN
+}
+
+impl ReplayPlayer {
+    pub fn is_host_bot(&self) -> bool {
+        return HOST_BOT_NAMES.contains(&self.battle_tag.as_str());
+    }
+}
+
+// The saver's own LeaveGame record is the only one closed by the local game. Host bots may
+// leave with the same reason, so they are skipped.
+pub(crate) fn identify_saving_player(players: &HashMap<u8, ReplayPlayer>, leaves: &[PlayerLeave], record_player_id: u8) -> (u8, SavingPlayerMethod) {
+    let is_host_bot = |player_id: &u8| players.get(player_id).is_some_and(|p| p.is_host_bot());
+
+    let local_leaves: Vec<u8> = leaves.iter()
+        .filter(|l| l.reason == LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME && !is_host_bot(&l.player_id))
+        .map(|l| l.player_id)
+        .collect();
+    if let [player_id] = local_leaves[..] {
+        return (player_id, SavingPlayerMethod::LOCAL_LEAVE_REASON);
+    }
+
+    // Otherwise the record holds the player who saved the replay, unless a host bot hosted the game
+    let record_is_candidate = local_leaves.is_empty() || local_leaves.contains(&record_player_id);
+    if players.contains_key(&record_player_id) && !is_host_bot(&record_player_id) && record_is_candidate {
+        return (record_player_id, SavingPlayerMethod::PLAYER_RECORD);
+    }
+
+    if let Some(leave) = leaves.iter().rev().find(|l| !is_host_bot(&l.player_id)) {
+        return (leave.player_id, SavingPlayerMethod::LAST_LEAVER);
+    }
+    return (0, SavingPlayerMethod::UNKNOWN);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LeaveResult, ReplayRecord};
+    use crate::testing::{leave, replay, summary};
+
+    const REMOTE: LeaveReason = LeaveReason::CONNECTION_CLOSED_BY_REMOTE_GAME;
+    const LOCAL: LeaveReason = LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME;
+
+    fn saving_player(battle_tags: &[&str], records: &[ReplayRecord]) -> (u8, SavingPlayerMethod) {
+        let replay = replay(&summary(battle_tags), records);
+        return (replay.metadata.saving_player_id, replay.metadata.saving_player_method);
+    }
+
+    #[test]
+    fn local_leave_reason() {
+        let records = [leave(1, REMOTE, LeaveResult::LEFT), leave(3, REMOTE, LeaveResult::LEFT), leave(2, LOCAL, LeaveResult::LEFT)];
+        assert_eq!(saving_player(&["Alice#1", "Bob#2", "Carol#3"], &records), (2, SavingPlayerMethod::LOCAL_LEAVE_REASON));
+    }
+
+    #[test]
+    fn player_record() {
+        assert_eq!(saving_player(&["Alice#1", "Bob#2"], &[leave(2, REMOTE, LeaveResult::LEFT)]), (1, SavingPlayerMethod::PLAYER_RECORD));
+        // Several local leaves are inconclusive, the record player is one of them
+        let records = [leave(2, LOCAL, LeaveResult::LEFT), leave(1, LOCAL, LeaveResult::LEFT)];
+        assert_eq!(saving_player(&["Alice#1", "Bob#2"], &records), (1, SavingPlayerMethod::PLAYER_RECORD));
+    }
+
+    #[test]
+    fn last_leaver() {
+        let records = [leave(2, LOCAL, LeaveResult::LEFT), leave(3, LOCAL, LeaveResult::LEFT), leave(1, REMOTE, LeaveResult::LEFT)];
+        assert_eq!(saving_player(&["Alice#1", "Bob#2", "Carol#3"], &records), (1, SavingPlayerMethod::LAST_LEAVER));
+    }
+
+    #[test]
+    fn host_bots_are_skipped() {
+        let players = ["FLO", "Alice#1", "Bob#2"];
+        let records = [leave(3, LOCAL, LeaveResult::LEFT), leave(1, LOCAL, LeaveResult::LEFT)];
+        assert_eq!(saving_player(&players, &records), (3, SavingPlayerMethod::LOCAL_LEAVE_REASON));
+        let records = [leave(3, REMOTE, LeaveResult::LEFT), leave(2, REMOTE, LeaveResult::LEFT), leave(1, REMOTE, LeaveResult::LEFT)];
+        assert_eq!(saving_player(&players, &records), (2, SavingPlayerMethod::LAST_LEAVER));
+        assert_eq!(saving_player(&["FLO"], &[]), (0, SavingPlayerMethod::UNKNOWN));
+    }
+
+    #[test]
+    fn saving_player_host() {
+        let is_host = |saving_player_id: u8| replay(&summary(&["Alice#1", "Bob#2"]), &[leave(saving_player_id, LOCAL, LeaveResult::LEFT)])
+            .metadata.is_saving_player_host;
+        assert!(is_host(1));
+        assert!(!is_host(2));
+    }
+}
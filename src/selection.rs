@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{Action, ActionKind, ItemId, ObjectIDs, SelectionMode};
+
+pub const NUM_CONTROL_GROUPS: usize = 10;
+
+/// Unit type and first unit of the active subgroup (tab selection).
+#[derive(Serialize, Debug, Clone)]
+pub struct Subgroup {
+    pub item_id: ItemId,
+    pub object: ObjectIDs
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PlayerSelection {
+    pub selection: Vec<ObjectIDs>,
+    pub groups: [Vec<ObjectIDs>; NUM_CONTROL_GROUPS],
+    pub subgroup: Option<Subgroup>
+}
+
+/// Follows the selection, control groups and active subgroup of every player
+/// and annotates ability actions with the units they were issued to.
+#[derive(Debug, Default)]
+pub struct SelectionTracker {
+    players: HashMap<u8, PlayerSelection>,
+    // Unit types learnt from subgroup selections
+    unit_types: HashMap<ObjectIDs, ItemId>
+}
+
+impl SelectionTracker {
+    pub fn new() -> SelectionTracker {
+        return SelectionTracker::default();
+    }
+
+    pub fn player(&self, player_id: u8) -> Option<&PlayerSelection> {
+        return self.players.get(&player_id);
+    }
+
+    pub fn players(&self) -> &HashMap<u8, PlayerSelection> {
+        return &self.players;
+    }
+
+    /// Updates the state of the acting player and sets `units` of ability actions.
+    pub fn apply(&mut self, action: &mut Action) {
+        let state = self.players.entry(action.player_id).or_default();
+        match &action.kind {
+            ActionKind::ChangeSelection { mode, objects } => {
+                match mode {
+                    SelectionMode::ADD => {
+                        for object in objects {
+                            if !state.selection.contains(object) {
+                                state.selection.push(object.clone());
+                            }
+                        }
+                    },
+                    SelectionMode::REMOVE => state.selection.retain(|o| !objects.contains(o)),
+                    SelectionMode::UNKNOWN => {}
+                }
+                if let Some(subgroup) = &state.subgroup {
+                    if !state.selection.contains(&subgroup.object) {
+                        state.subgroup = None;
+                    }
+                }
+            },
+            ActionKind::GroupAssign { group, objects } => {
+                if let Some(units) = state.groups.get_mut(*group as usize) {
+                    *units = objects.clone();
+                }
+            },
+            ActionKind::GroupSelect { group, .. } => {
+                if let Some(units) = state.groups.get(*group as usize) {
+                    state.selection = units.clone();
+                    state.subgroup = None;
+                }
+            },
+            ActionKind::SelectSubgroup { item_id, object } => {
+                self.unit_types.insert(object.clone(), item_id.clone());
+                state.subgroup = Some(Subgroup { item_id: item_id.clone(), object: object.clone() });
+            },
+            ActionKind::AbilityBasic { .. } |
+            ActionKind::AbilityWithTargetLocation { .. } |
+            ActionKind::AbilityWithTargetObject { .. } |
+            ActionKind::ItemTransfer { .. } |
+            ActionKind::AbilityWithTwoTargetLocations { .. } => {
+                // Orders go to the active subgroup. Units of unknown type are kept, as they may belong to it.
+                let units = match &state.subgroup {
+                    Some(subgroup) => state.selection.iter()
+                        .filter(|o| self.unit_types.get(*o).is_none_or(|t| *t == subgroup.item_id))
+                        .cloned()
+                        .collect(),
+                    None => state.selection.clone()
+                };
+                action.units = Some(units);
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(id: u32) -> ObjectIDs {
+        return ObjectIDs { id1: id, id2: id };
+    }
+
+    fn apply(tracker: &mut SelectionTracker, kind: ActionKind) -> Option<Vec<ObjectIDs>> {
+        let mut action = Action { player_id: 1, timestamp: 0, kind, units: None };
+        tracker.apply(&mut action);
+        return action.units;
+    }
+
+    fn select(mode: SelectionMode, ids: &[u32]) -> ActionKind {
+        return ActionKind::ChangeSelection { mode, objects: ids.iter().map(|id| object(*id)).collect() };
+    }
+
+    fn subgroup(unit_type: &str, id: u32) -> ActionKind {
+        return ActionKind::SelectSubgroup { item_id: ItemId::FourCC(unit_type.to_string()), object: object(id) };
+    }
+
+    fn order() -> ActionKind {
+        return ActionKind::AbilityBasic { flags: 0, item_id: ItemId::Order(0xD0003), unknown_a: 0, unknown_b: 0 };
+    }
+
+    fn selection(tracker: &SelectionTracker) -> Vec<ObjectIDs> {
+        return tracker.player(1).unwrap().selection.clone();
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut tracker = SelectionTracker::new();
+        apply(&mut tracker, select(SelectionMode::ADD, &[1, 2]));
+        apply(&mut tracker, select(SelectionMode::ADD, &[2, 3]));
+        assert_eq!(selection(&tracker), [object(1), object(2), object(3)]);
+        apply(&mut tracker, select(SelectionMode::REMOVE, &[1, 3]));
+        assert_eq!(selection(&tracker), [object(2)]);
+        assert_eq!(apply(&mut tracker, order()), Some(vec![object(2)]));
+        assert!(tracker.player(2).is_none());
+    }
+
+    #[test]
+    fn control_groups() {
+        let mut tracker = SelectionTracker::new();
+        apply(&mut tracker, ActionKind::GroupAssign { group: 1, objects: vec![object(1), object(2)] });
+        apply(&mut tracker, select(SelectionMode::ADD, &[3]));
+        apply(&mut tracker, ActionKind::GroupSelect { group: 1, unknown: 3 });
+        assert_eq!(selection(&tracker), [object(1), object(2)]);
+        // Groups beyond the ten control groups are ignored
+        apply(&mut tracker, ActionKind::GroupSelect { group: 10, unknown: 3 });
+        assert_eq!(selection(&tracker), [object(1), object(2)]);
+    }
+
+    #[test]
+    fn orders_go_to_the_subgroup() {
+        let mut tracker = SelectionTracker::new();
+        apply(&mut tracker, select(SelectionMode::ADD, &[1, 2, 3]));
+        apply(&mut tracker, subgroup("hfoo", 2));
+        apply(&mut tracker, subgroup("hpea", 1));
+        // Unit 3 is of unknown type and may belong to the subgroup
+        assert_eq!(apply(&mut tracker, order()), Some(vec![object(1), object(3)]));
+        apply(&mut tracker, subgroup("hfoo", 2));
+        assert_eq!(apply(&mut tracker, order()), Some(vec![object(2), object(3)]));
+
+        // Removing the first unit of the subgroup ends it
+        apply(&mut tracker, select(SelectionMode::REMOVE, &[2]));
+        assert!(tracker.player(1).unwrap().subgroup.is_none());
+        assert_eq!(apply(&mut tracker, order()), Some(vec![object(1), object(3)]));
+    }
+}
@@ -0,0 +1,111 @@
+// Replays written with the ReplayWriter for the unit tests
+use std::collections::HashMap;
+use num_traits::FromPrimitive;
+use crate::*;
+
+// Players 1 - n in slots 0 - n-1, the odd players on team 0 and the even players on team 1
+pub(crate) fn summary(battle_tags: &[&str]) -> ReplaySummary {
+    let mut players: HashMap<u8, ReplayPlayer> = HashMap::new();
+    let mut slots: Vec<Slot> = vec![];
+    for (i, battle_tag) in battle_tags.iter().enumerate() {
+        let player_id = i as u8 + 1;
+        players.insert(player_id, ReplayPlayer {
+            battle_tag: battle_tag.to_string(),
+            leave_reason: LeaveReason::UNKNOWN(0),
+            result_byte: 0,
+            left_at: 0,
+            reforged: None,
+            additional_data: vec![0x00],
+            record_unknown: 0
+        });
+        slots.push(Slot {
+            player_id,
+            map_download_percent: 100,
+            status: SlotStatus::OCCUPIED,
+            is_computer: false,
+            team_index: i as u8 % 2,
+            color: SlotColor::from_u8(player_id).unwrap(),
+            race: SlotRace::HUMAN,
+            race_flags: SlotRace::HUMAN as u8,
+            ai_strength: ComputerAIStrength::NORMAL,
+            handicap_percent: 100,
+            raw: [0; 9]
+        });
+    }
+    return ReplaySummary {
+        version: 1,
+        header: ReplayHeader {
+            magic: String::from("Warcraft III recorded game"),
+            header_size: 68,
+            compressed_size: 0,
+            header_version: 1,
+            decompressed_size: 0,
+            num_data_blocks: 0,
+            product_id: String::from("W3XP"),
+            patch_version: 10032,
+            build_number: 6114,
+            flags: 0x8000,
+            duration_ms: 0,
+            crc32: 0
+        },
+        record_player_id: 1,
+        record_player_is_host: true,
+        game_name: String::from("OZE test game"),
+        map_name: String::from("Maps\\Test.w3x"),
+        game_creator_battle_tag: battle_tags[0].to_string(),
+        game_settings: GameSettings {
+            game_speed: 2,
+            vis_default: true,
+            fixed_teams: 3,
+            ..Default::default()
+        },
+        game_start_info: GameStartInfo {
+            num_players_slots: slots.len() as u32,
+            game_type: GameType::CUSTOM,
+            selection_mode: SlotSelectionMode::TEAM_AND_RACE_SELECTABLE,
+            start_spot_count: slots.len() as u8,
+            ..Default::default()
+        },
+        slots,
+        players,
+        reforged_records: vec![]
+    };
+}
+
+// TimeSlot with a command block of the player, or without any if there are no actions.
+// Timestamps are left to the decoder.
+pub(crate) fn time_slot(increment: u16, player_id: u8, kinds: Vec<ActionKind>) -> ReplayRecord {
+    let actions: Vec<Action> = kinds.into_iter().map(|kind| Action { player_id, timestamp: 0, kind, units: None }).collect();
+    let command_blocks = match actions.is_empty() {
+        true => vec![],
+        false => vec![CommandBlock { player_id, actions }]
+    };
+    return ReplayRecord::TimeSlot(TimeSlot { timestamp: 0, increment, command_blocks });
+}
+
+pub(crate) fn chat(player_id: u8, channel: ChatChannel, message: &str) -> ReplayRecord {
+    return ReplayRecord::Chat(ChatMessage {
+        sender_player_id: player_id,
+        channel,
+        recipient_player_id: None,
+        flag: None,
+        message: message.to_string(),
+        timestamp: 0
+    });
+}
+
+pub(crate) fn leave(player_id: u8, reason: LeaveReason, result: LeaveResult) -> ReplayRecord {
+    return ReplayRecord::LeaveGame(PlayerLeave { player_id, reason, result: result as u32, unknown: 0, timestamp: 0 });
+}
+
+pub(crate) fn write(summary: &ReplaySummary, records: &[ReplayRecord]) -> Vec<u8> {
+    let mut writer = ReplayWriter::new(summary).unwrap();
+    for record in records {
+        writer.write_record(record).unwrap();
+    }
+    return writer.into_bytes().unwrap();
+}
+
+pub(crate) fn replay(summary: &ReplaySummary, records: &[ReplayRecord]) -> Replay {
+    return Replay::try_from_bytes(&write(summary, records)).unwrap();
+}
@@ -0,0 +1,390 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use log::info;
+use serde::Serialize;
+use crate::{ActionKind, Replay, SlotStatus};
+
+// Game cache file and mission key prefix of W3MMD messages
+const W3MMD_FILENAME: &str = "MMD.Dat";
+const W3MMD_MESSAGE_PREFIX: &str = "val:";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum W3mmdType {
+    INT,
+    REAL,
+    STRING
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum W3mmdGoal {
+    HIGH,
+    LOW,
+    NONE
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum W3mmdSuggestion {
+    NONE,
+    TRACK,
+    LEADERBOARD
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum W3mmdFlag {
+    WINNER,
+    LOSER,
+    DRAWER,
+    LEAVER,
+    PRACTICING
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum W3mmdValue {
+    Int(i64),
+    Real(f64),
+    String(String)
+}
+
+#[derive(Serialize, Debug)]
+pub struct W3mmdVariable {
+    pub name: String,
+    pub value_type: W3mmdType,
+    pub goal: W3mmdGoal,
+    pub suggestion: W3mmdSuggestion
+}
+
+#[derive(Serialize, Debug)]
+pub struct W3mmdEventDefinition {
+    pub name: String,
+    pub arguments: Vec<String>,
+    pub format: String
+}
+
+#[derive(Serialize, Debug)]
+pub struct W3mmdEvent {
+    pub timestamp: u64,
+    pub name: String,
+    pub arguments: Vec<String>,
+    /// Format of the event definition with the arguments filled in
+    pub text: Option<String>
+}
+
+#[derive(Serialize, Debug)]
+pub struct W3mmdPlayer {
+    /// Player number used by the map, i.e. the slot index
+    pub pid: u32,
+    /// Replay player id of the slot
+    pub player_id: Option<u8>,
+    pub name: String,
+    pub flags: Vec<W3mmdFlag>,
+    pub values: HashMap<String, W3mmdValue>
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct W3mmdData {
+    pub minimum_version: Option<u32>,
+    pub version: Option<u32>,
+    pub variables: Vec<W3mmdVariable>,
+    pub event_definitions: Vec<W3mmdEventDefinition>,
+    pub events: Vec<W3mmdEvent>,
+    pub players: BTreeMap<u32, W3mmdPlayer>,
+    pub custom: Vec<String>
+}
+
+impl W3mmdData {
+    fn player(&mut self, pid: u32, replay: &Replay) -> &mut W3mmdPlayer {
+        return self.players.entry(pid).or_insert_with(|| {
+            let player_id = replay.slots.get(pid as usize)
+                .filter(|slot| matches!(slot.status, SlotStatus::OCCUPIED))
+                .map(|slot| slot.player_id);
+            W3mmdPlayer {
+                pid,
+                player_id,
+                name: String::new(),
+                flags: vec![],
+                values: HashMap::new()
+            }
+        });
+    }
+
+    fn apply(&mut self, message: &str, timestamp: u64, replay: &Replay) -> Option<()> {
+        let tokens = split_message(message);
+        let args: Vec<&str> = tokens.iter().map(|t| t.as_str()).collect();
+        match args[..] {
+            ["init", "version", minimum, current] => {
+                self.minimum_version = minimum.parse().ok();
+                self.version = current.parse().ok();
+            },
+            ["init", "pid", pid, name] => {
+                self.player(pid.parse().ok()?, replay).name = name.to_string();
+            },
+            ["DefVarP", name, value_type, goal, suggestion] => {
+                self.variables.push(W3mmdVariable {
+                    name: name.to_string(),
+                    value_type: match value_type {
+                        "int" => W3mmdType::INT,
+                        "real" => W3mmdType::REAL,
+                        "string" => W3mmdType::STRING,
+                        _ => return None
+                    },
+                    goal: match goal {
+                        "high" => W3mmdGoal::HIGH,
+                        "low" => W3mmdGoal::LOW,
+                        _ => W3mmdGoal::NONE
+                    },
+                    suggestion: match suggestion {
+                        "track" => W3mmdSuggestion::TRACK,
+                        "leaderboard" => W3mmdSuggestion::LEADERBOARD,
+                        _ => W3mmdSuggestion::NONE
+                    }
+                });
+            },
+            ["VarP", pid, name, operation, value] => {
+                let value_type = self.variables.iter().find(|v| v.name == name)?.value_type;
+                let value = match value_type {
+                    W3mmdType::INT => W3mmdValue::Int(value.parse().ok()?),
+                    W3mmdType::REAL => W3mmdValue::Real(value.parse().ok()?),
+                    W3mmdType::STRING => W3mmdValue::String(value.to_string())
+                };
+                let values = &mut self.player(pid.parse().ok()?, replay).values;
+                let current = values.entry(name.to_string()).or_insert(match value_type {
+                    W3mmdType::INT => W3mmdValue::Int(0),
+                    W3mmdType::REAL => W3mmdValue::Real(0.0),
+                    W3mmdType::STRING => W3mmdValue::String(String::new())
+                });
+                *current = match (operation, &*current, value) {
+                    ("=", _, value) => value,
+                    // The values are up to the map, an overflow makes the message invalid
+                    ("+=", W3mmdValue::Int(a), W3mmdValue::Int(b)) => W3mmdValue::Int(a.checked_add(b)?),
+                    ("-=", W3mmdValue::Int(a), W3mmdValue::Int(b)) => W3mmdValue::Int(a.checked_sub(b)?),
+                    ("+=", W3mmdValue::Real(a), W3mmdValue::Real(b)) => W3mmdValue::Real(a + b),
+                    ("-=", W3mmdValue::Real(a), W3mmdValue::Real(b)) => W3mmdValue::Real(a - b),
+                    _ => return None
+                };
+            },
+            ["FlagP", pid, flag] => {
+                let flag = match flag {
+                    "winner" => W3mmdFlag::WINNER,
+                    "loser" => W3mmdFlag::LOSER,
+                    "drawer" => W3mmdFlag::DRAWER,
+                    "leaver" => W3mmdFlag::LEAVER,
+                    "practicing" => W3mmdFlag::PRACTICING,
+                    _ => return None
+                };
+                let flags = &mut self.player(pid.parse().ok()?, replay).flags;
+                if !flags.contains(&flag) {
+                    flags.push(flag);
+                }
+            },
+            ["DefEvent", name, argument_count, ..] => {
+                let argument_count: usize = argument_count.parse().ok()?;
+                let rest = &args[3..];
+                if rest.len() != argument_count + 1 {
+                    return None;
+                }
+                self.event_definitions.push(W3mmdEventDefinition {
+                    name: name.to_string(),
+                    arguments: rest[..argument_count].iter().map(|a| a.to_string()).collect(),
+                    format: rest[argument_count].to_string()
+                });
+            },
+            ["Event", name, ..] => {
+                let arguments: Vec<String> = args[2..].iter().map(|a| a.to_string()).collect();
+                let text = self.event_definitions.iter()
+                    .find(|d| d.name == name && d.arguments.len() == arguments.len())
+                    .map(|d| format_event(&d.format, &arguments));
+                self.events.push(W3mmdEvent {
+                    timestamp,
+                    name: name.to_string(),
+                    arguments,
+                    text
+                });
+            },
+            ["Custom", ..] => self.custom.push(args[1..].join(" ")),
+            ["Blank"] => {},
+            _ => return None
+        }
+        return Some(());
+    }
+}
+
+// Arguments are separated by spaces, spaces and backslashes inside are escaped with a backslash
+fn split_message(message: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![String::new()];
+    let mut escaped = false;
+    for c in message.chars() {
+        match c {
+            _ if escaped => {
+                tokens.last_mut().unwrap().push(c);
+                escaped = false;
+            },
+            '\\' => escaped = true,
+            ' ' => tokens.push(String::new()),
+            _ => tokens.last_mut().unwrap().push(c)
+        }
+    }
+    return tokens;
+}
+
+// Replaces the placeholders {0}, {1}, ... of an event format with the arguments
+fn format_event(format: &str, arguments: &[String]) -> String {
+    let mut text = format.to_string();
+    for (i, argument) in arguments.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", i), argument);
+    }
+    return text;
+}
+
+impl Replay {
+    /// Interprets the W3MMD messages a map sent through the game cache, if there are any.
+    pub fn w3mmd(&self) -> Option<W3mmdData> {
+        let mut data = W3mmdData::default();
+        let mut seen_messages: HashSet<&str> = HashSet::new();
+        for action in &self.actions {
+            let ActionKind::SyncStoreInteger { filename, mission_key, key, .. } = &action.kind else {
+                continue;
+            };
+            if filename != W3MMD_FILENAME || !mission_key.starts_with(W3MMD_MESSAGE_PREFIX) {
+                continue;
+            }
+            // Messages are numbered, every message only counts once
+            if !seen_messages.insert(mission_key) {
+                continue;
+            }
+            if data.apply(key, action.timestamp, self).is_none() {
+                info!("Invalid W3MMD message: {:?}", key);
+            }
+        }
+        if seen_messages.is_empty() {
+            return None;
+        }
+        return Some(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReplayRecord;
+    use crate::testing::{replay, summary, time_slot};
+
+    fn store(message_number: usize, message: &str) -> ActionKind {
+        return ActionKind::SyncStoreInteger {
+            filename: String::from(W3MMD_FILENAME),
+            mission_key: format!("{}{}", W3MMD_MESSAGE_PREFIX, message_number),
+            key: message.to_string(),
+            value: 0
+        };
+    }
+
+    // Every message in a TimeSlot of its own, numbered in order
+    fn w3mmd(messages: &[&str]) -> W3mmdData {
+        let records: Vec<ReplayRecord> = messages.iter().enumerate()
+            .map(|(i, message)| time_slot(100, 1, vec![store(i, message)]))
+            .collect();
+        return replay(&summary(&["Alice#123", "Bob#456"]), &records).w3mmd().unwrap();
+    }
+
+    #[test]
+    fn variables() {
+        let data = w3mmd(&[
+            "DefVarP kills int high leaderboard",
+            "DefVarP gold real none track",
+            "DefVarP hero string none none",
+            "VarP 0 kills = 5",
+            "VarP 0 kills += 3",
+            "VarP 0 kills -= 1",
+            "VarP 1 kills -= 2",
+            "VarP 0 gold += 1.5",
+            "VarP 0 hero = Archmage",
+            "VarP 0 hero += Paladin",
+            "VarP 0 deaths = 1"
+        ]);
+        assert_eq!(data.variables.len(), 3);
+        assert_eq!(data.variables[0].value_type, W3mmdType::INT);
+        assert_eq!(data.variables[0].goal, W3mmdGoal::HIGH);
+        assert_eq!(data.variables[0].suggestion, W3mmdSuggestion::LEADERBOARD);
+        assert_eq!(data.variables[1].suggestion, W3mmdSuggestion::TRACK);
+        let values = &data.players[&0].values;
+        assert_eq!(values["kills"], W3mmdValue::Int(7));
+        assert_eq!(values["gold"], W3mmdValue::Real(1.5));
+        // Strings can only be set
+        assert_eq!(values["hero"], W3mmdValue::String(String::from("Archmage")));
+        assert!(!values.contains_key("deaths"));
+        assert_eq!(data.players[&1].values["kills"], W3mmdValue::Int(-2));
+    }
+
+    #[test]
+    fn overflowing_values_are_ignored() {
+        let data = w3mmd(&[
+            "DefVarP kills int high none",
+            &format!("VarP 0 kills = {}", i64::MAX),
+            "VarP 0 kills += 1",
+            &format!("VarP 1 kills = {}", i64::MIN),
+            "VarP 1 kills -= 1"
+        ]);
+        assert_eq!(data.players[&0].values["kills"], W3mmdValue::Int(i64::MAX));
+        assert_eq!(data.players[&1].values["kills"], W3mmdValue::Int(i64::MIN));
+    }
+
+    #[test]
+    fn flags() {
+        let data = w3mmd(&["FlagP 0 winner", "FlagP 1 loser", "FlagP 1 leaver", "FlagP 1 leaver", "FlagP 1 quitter"]);
+        assert_eq!(data.players[&0].flags, [W3mmdFlag::WINNER]);
+        assert_eq!(data.players[&1].flags, [W3mmdFlag::LOSER, W3mmdFlag::LEAVER]);
+        // pid is the slot index
+        assert_eq!(data.players[&0].player_id, Some(1));
+        assert_eq!(data.players[&1].player_id, Some(2));
+    }
+
+    #[test]
+    fn events() {
+        let data = w3mmd(&[
+            "DefEvent kill 2 killer victim {0}\\ killed\\ {1}",
+            "Event kill 0 1",
+            "Event kill 0",
+            "Event rosh"
+        ]);
+        assert_eq!(data.event_definitions[0].arguments, ["killer", "victim"]);
+        assert_eq!(data.event_definitions[0].format, "{0} killed {1}");
+        assert_eq!(data.events.len(), 3);
+        assert_eq!(data.events[0].arguments, ["0", "1"]);
+        assert_eq!(data.events[0].text.as_deref(), Some("0 killed 1"));
+        // The argument count does not match the definition
+        assert_eq!(data.events[1].text, None);
+        assert_eq!(data.events[2].text, None);
+        assert!(data.events[0].timestamp < data.events[1].timestamp);
+    }
+
+    #[test]
+    fn init() {
+        let data = w3mmd(&["init version 0 1", "init pid 0 Alice#123", "init pid 1 Bob\\ Smith"]);
+        assert_eq!(data.minimum_version, Some(0));
+        assert_eq!(data.version, Some(1));
+        assert_eq!(data.players[&0].name, "Alice#123");
+        assert_eq!(data.players[&1].name, "Bob Smith");
+    }
+
+    #[test]
+    fn messages_count_once() {
+        let records = [
+            time_slot(100, 1, vec![store(0, "DefVarP kills int high none"), store(1, "VarP 0 kills += 1")]),
+            // Sent again by another player
+            time_slot(100, 2, vec![store(1, "VarP 0 kills += 1")]),
+            time_slot(100, 1, vec![store(2, "VarP 0 kills += 1")])
+        ];
+        let data = replay(&summary(&["Alice#123", "Bob#456"]), &records).w3mmd().unwrap();
+        assert_eq!(data.players[&0].values["kills"], W3mmdValue::Int(2));
+    }
+
+    #[test]
+    fn no_messages() {
+        let records = [time_slot(100, 1, vec![ActionKind::SyncStoreInteger {
+            filename: String::from("Other.Dat"),
+            mission_key: String::from("val:0"),
+            key: String::from("init pid 0 Alice#123"),
+            value: 0
+        }])];
+        assert!(replay(&summary(&["Alice#123", "Bob#456"]), &records).w3mmd().is_none());
+    }
+}
@@ -0,0 +1,985 @@
+use std::io::Write;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use log::warn;
+use crate::*;
+use crate::blocks::BLOCK_SIZE;
+use crate::header::{block_checksum, header_checksum, REPLAY_MAGIC};
+
+/// Decision of a chat filter, see [`ReplayWriter::set_chat_filter`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatFilterResult {
+    Keep,
+    Drop,
+    Replace(String)
+}
+
+type ChatFilter<'a> = dyn FnMut(&ChatMessage) -> ChatFilterResult + 'a;
+
+/// Encodes a replay from a [`ReplaySummary`] and the ReplayData records, the counterpart of [`ReplayDecoder`].
+///
+/// Values the decoder does not interpret are kept by it and written back as read.
+/// Use [`ReplayWriter::from_raw`] to reproduce the decompressed data byte for byte.
+pub struct ReplayWriter<'a> {
+    header: ReplayHeader,
+    data: Vec<u8>,
+    duration_ms: Option<u32>,
+    chat_filter: Option<Box<ChatFilter<'a>>>
+}
+
+impl<'a> ReplayWriter<'a> {
+    /// Encodes the records in front of the ReplayData (4.0 - 4.10).
+    pub fn new(summary: &ReplaySummary) -> Result<ReplayWriter<'a>, ReplayError> {
+        let mut data: Vec<u8> = vec![];
+        write_game_info(&mut data, summary)?;
+        return Ok(ReplayWriter {
+            header: summary.header.clone(),
+            data,
+            duration_ms: None,
+            chat_filter: None
+        });
+    }
+
+    /// Encodes a parsed replay, e.g. after [`Anonymiser::anonymise_replay`].
+    ///
+    /// The TimeSlots are rebuilt from their increments and command blocks, taking the actions and the trigger chat
+    /// of the replay in order, so actions and trigger chat messages may be changed but not added or removed.
+    /// Trigger chat is written after the other actions of its command block.
+    /// The other records are written in the order of their timestamps. Like [`ReplayWriter::from_raw`], the data is not filtered.
+    pub fn from_replay(replay: &Replay) -> Result<ReplayWriter<'a>, ReplayError> {
+        let summary = ReplaySummary {
+            version: replay.version,
+            header: replay.header.clone(),
+            record_player_id: replay.metadata.record_player_id,
+            record_player_is_host: replay.metadata.record_player_is_host,
+            game_name: replay.metadata.game_name.clone(),
+            map_name: replay.metadata.map_name.clone(),
+            game_creator_battle_tag: replay.metadata.game_creator_battle_tag.clone(),
+            game_settings: replay.game_settings.clone(),
+            game_start_info: replay.game_start_info.clone(),
+            slots: replay.slots.clone(),
+            players: replay.players.clone(),
+            reforged_records: replay.reforged_records.clone()
+        };
+        let mut writer = ReplayWriter::new(&summary)?;
+
+        let mut records: Vec<ReplayRecord> = replay.startup_records.iter().cloned().map(ReplayRecord::Startup)
+            .chain(replay.chat.iter().filter(|m| m.channel != ChatChannel::Trigger).cloned().map(ReplayRecord::Chat))
+            .chain(replay.leaves.iter().cloned().map(ReplayRecord::LeaveGame))
+            .chain(replay.other_records.iter().cloned().map(ReplayRecord::Other))
+            .collect();
+        records.sort_by_key(record_timestamp);
+        let mut records = records.into_iter().peekable();
+
+        // Records get the timestamp of the TimeSlot in front of them
+        let mut actions = replay.actions.iter();
+        let mut trigger_chat = replay.chat.iter().filter(|m| m.channel == ChatChannel::Trigger);
+        let mut timestamp = 0;
+        for time_slot in &replay.time_slots {
+            while let Some(record) = records.next_if(|record| record_timestamp(record) <= timestamp) {
+                writer.write_record(&record)?;
+            }
+            let mut command_blocks: Vec<u8> = vec![];
+            for command_block in &time_slot.command_blocks {
+                let mut block_actions: Vec<u8> = vec![];
+                for action in actions.by_ref().take(command_block.actions) {
+                    write_action(&mut block_actions, &action.kind)?;
+                }
+                for ((unknown_a, unknown_b), message) in command_block.trigger_chat.iter().zip(trigger_chat.by_ref()) {
+                    write_action(&mut block_actions, &ActionKind::TriggerChat { unknown_a: *unknown_a, unknown_b: *unknown_b, message: message.message.clone() })?;
+                }
+                write_command_block(&mut command_blocks, command_block.player_id, &block_actions)?;
+            }
+            write_time_slot(&mut writer.data, time_slot.increment, &command_blocks)?;
+            writer.duration_ms = Some(time_slot.timestamp as u32);
+            timestamp = time_slot.timestamp;
+        }
+        for record in records {
+            writer.write_record(&record)?;
+        }
+        return Ok(writer);
+    }
+
+    /// Writes the decompressed data of a replay read with [`ParseOptions::keep_raw_records`] unchanged.
+    /// Only the compressed blocks and the header fields describing them differ from the original file.
+    pub fn from_raw(raw: &RawReplay) -> Result<ReplayWriter<'a>, ReplayError> {
+        let (header, _) = header::read_header(&mut raw.header.as_slice(), &ParseOptions::default())?;
+        return Ok(ReplayWriter {
+            header,
+            data: raw.data(),
+            duration_ms: None,
+            chat_filter: None
+        });
+    }
+
+    /// Appends a record to the ReplayData.
+    ///
+    /// Trigger chat is part of the 0x60 actions of its TimeSlot, so chat records of [`ChatChannel::Trigger`],
+    /// e.g. from [`Replay::chat`], are not written.
+    pub fn write_record(&mut self, record: &ReplayRecord) -> Result<(), ReplayError> {
+        match self.chat_filter.as_mut() {
+            Some(filter) => write_record(&mut self.data, record, filter.as_mut())?,
+            None => write_record(&mut self.data, record, &mut |_| ChatFilterResult::Keep)?
+        }
+        if let ReplayRecord::TimeSlot(time_slot) = record {
+            self.duration_ms = Some(time_slot.timestamp as u32);
+        }
+        return Ok(());
+    }
+
+    /// Calls the filter for every chat message written, 0x20 records as well as the 0x60 actions of trigger chat,
+    /// which get the channel [`ChatChannel::Trigger`].
+    ///
+    /// Trigger chat is seen by the map, so maps reacting to chat messages may play back differently if it is changed.
+    /// Data written with [`ReplayWriter::from_raw`] is not filtered.
+    pub fn set_chat_filter<F>(&mut self, filter: F) where F: FnMut(&ChatMessage) -> ChatFilterResult + 'a {
+        self.chat_filter = Some(Box::new(filter));
+    }
+
+    /// Bytes [`ReplayWriter::write_record`] appends for a record,
+    /// e.g. to compare the decoded view of a record with its [`RawRecord`].
+    pub fn encode_record(record: &ReplayRecord) -> Result<Vec<u8>, ReplayError> {
+        let mut data: Vec<u8> = vec![];
+        write_record(&mut data, record, &mut |_| ChatFilterResult::Keep)?;
+        return Ok(data);
+    }
+
+    /// Compresses the data into blocks and returns the complete replay file.
+    pub fn into_bytes(self) -> Result<Vec<u8>, ReplayError> {
+        let mut data = self.data;
+        let num_data_blocks = data.len().div_ceil(BLOCK_SIZE).max(1);
+        data.resize(num_data_blocks * BLOCK_SIZE, 0);
+
+        // 3.0 [Data block header] + compressed data
+        let mut blocks: Vec<u8> = vec![];
+        for block in data.chunks(BLOCK_SIZE) {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(block).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let mut block_header: Vec<u8> = vec![];
+            write_dword(&mut block_header, compressed.len() as u32);
+            write_dword(&mut block_header, block.len() as u32);
+            let checksum = block_checksum(&block_header, &compressed);
+            write_dword(&mut block_header, checksum);
+            blocks.extend_from_slice(&block_header);
+            blocks.extend_from_slice(&compressed);
+        }
+
+        // 2.0 [Header] + 2.1 [SubHeader] in the version of the source replay
+        let header = &self.header;
+        let header_size = header.total_length();
+        let mut replay: Vec<u8> = REPLAY_MAGIC.to_vec();
+        replay.push(0x00);
+        write_dword(&mut replay, header_size as u32);
+        write_dword(&mut replay, checked_u32("replay size", header_size + blocks.len())?);
+        write_dword(&mut replay, header.header_version);
+        write_dword(&mut replay, checked_u32("decompressed size", data.len())?);
+        write_dword(&mut replay, num_data_blocks as u32);
+        if header.header_version == 0 {
+            write_word(&mut replay, 0);
+            write_word(&mut replay, checked_u16("patch version", header.patch_version as usize)?);
+        }
+        else {
+            // Product id is stored reversed, e.g. "PX3W"
+            let mut product_id = [0u8; 4];
+            for (byte, c) in product_id.iter_mut().zip(header.product_id.bytes()) {
+                *byte = c;
+            }
+            product_id.reverse();
+            replay.extend_from_slice(&product_id);
+            write_dword(&mut replay, header.patch_version);
+        }
+        write_word(&mut replay, header.build_number);
+        write_word(&mut replay, header.flags);
+        write_dword(&mut replay, self.duration_ms.unwrap_or(header.duration_ms));
+        write_dword(&mut replay, 0);
+        let crc = header_checksum(&replay);
+        let crc_offset = replay.len() - 4;
+        replay[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+
+        replay.append(&mut blocks);
+        return Ok(replay);
+    }
+
+    /// Compresses the data into blocks and writes the complete replay file.
+    pub fn finish<W: Write>(self, mut writer: W) -> Result<(), ReplayError> {
+        return writer.write_all(&self.into_bytes()?).map_err(|source| ReplayError::Write { source });
+    }
+}
+
+fn record_timestamp(record: &ReplayRecord) -> u64 {
+    return match record {
+        ReplayRecord::Startup(startup_record) => startup_record.timestamp,
+        ReplayRecord::LeaveGame(leave) => leave.timestamp,
+        ReplayRecord::TimeSlot(time_slot) => time_slot.timestamp,
+        ReplayRecord::Chat(message) => message.timestamp,
+        ReplayRecord::Other(other_record) => other_record.timestamp
+    };
+}
+
+// Lengths and counts are checked instead of being truncated to their field
+fn checked_u8(field: &'static str, value: usize) -> Result<u8, ReplayError> {
+    return u8::try_from(value).map_err(|_| ReplayError::ValueTooLarge { field, value: value as u64 });
+}
+
+fn checked_u16(field: &'static str, value: usize) -> Result<u16, ReplayError> {
+    return u16::try_from(value).map_err(|_| ReplayError::ValueTooLarge { field, value: value as u64 });
+}
+
+fn checked_u32(field: &'static str, value: usize) -> Result<u32, ReplayError> {
+    return u32::try_from(value).map_err(|_| ReplayError::ValueTooLarge { field, value: value as u64 });
+}
+
+fn write_word(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dword(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dword_float(data: &mut Vec<u8>, value: f32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_nullterminated_string(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(value.as_bytes());
+    data.push(0x00);
+}
+
+fn write_ability_itemid(data: &mut Vec<u8>, item_id: &ItemId) {
+    match item_id {
+        ItemId::Order(order) => write_dword(data, *order),
+        ItemId::FourCC(code) => {
+            // FourCC codes are stored reversed
+            let mut buf = [0u8; 4];
+            for (byte, c) in buf.iter_mut().zip(code.bytes()) {
+                *byte = c;
+            }
+            buf.reverse();
+            data.extend_from_slice(&buf);
+        }
+    }
+}
+
+fn write_location(data: &mut Vec<u8>, location: &MapLocation) {
+    write_dword_float(data, location.x);
+    write_dword_float(data, location.y);
+}
+
+fn write_object_ids(data: &mut Vec<u8>, object: &ObjectIDs) {
+    write_dword(data, object.id1);
+    write_dword(data, object.id2);
+}
+
+// Inverse of decode_gamesettings: every byte gets odd, a mask byte in front of each 7 bytes records which were even
+fn encode_gamesettings(dec: &[u8]) -> Vec<u8> {
+    let mut enc: Vec<u8> = vec![];
+    for chunk in dec.chunks(7) {
+        let mut mask: u8 = 0x01;
+        let mut encoded_chunk: Vec<u8> = vec![];
+        for (i, byte) in chunk.iter().enumerate() {
+            if byte % 2 == 0 {
+                encoded_chunk.push(byte + 1);
+            }
+            else {
+                encoded_chunk.push(*byte);
+                mask |= 0x1 << (i + 1);
+            }
+        }
+        enc.push(mask);
+        enc.append(&mut encoded_chunk);
+    }
+    enc.push(0x00);
+    return enc;
+}
+
+// 4.4 [GameSettings] + 4.5 [Map&CreatorName]
+fn game_settings_bytes(summary: &ReplaySummary) -> Vec<u8> {
+    let settings = &summary.game_settings;
+    let mut buf = settings.raw.to_vec();
+    buf[0] = (buf[0] & !0x03) | (settings.game_speed & 0x03);
+    buf[1] = (buf[1] & !0x7F)
+        | settings.vis_hide_terrain as u8
+        | (settings.vis_map_explored as u8) << 1
+        | (settings.vis_always_visible as u8) << 2
+        | (settings.vis_default as u8) << 3
+        | (settings.obs_mode & 0x03) << 4
+        | (settings.teams_together as u8) << 6;
+    buf[2] = (buf[2] & !0x06) | (settings.fixed_teams & 0x03) << 1;
+    buf[3] = (buf[3] & !0x47)
+        | settings.shared_unit_control as u8
+        | (settings.random_hero as u8) << 1
+        | (settings.random_races as u8) << 2
+        | (settings.obs_referees as u8) << 6;
+    write_dword(&mut buf, settings.map_checksum);
+    write_nullterminated_string(&mut buf, &summary.map_name);
+    write_nullterminated_string(&mut buf, &summary.game_creator_battle_tag);
+    buf.extend_from_slice(&settings.extra_data);
+    return buf;
+}
+
+fn write_additional_data(data: &mut Vec<u8>, player: Option<&ReplayPlayer>) -> Result<(), ReplayError> {
+    let additional_data = player.map(|p| p.additional_data.as_slice()).unwrap_or_default();
+    data.push(checked_u8("player record additional data length", additional_data.len())?);
+    data.extend_from_slice(additional_data);
+    return Ok(());
+}
+
+// 4.0 - 4.10: everything in front of the ReplayData
+fn write_game_info(data: &mut Vec<u8>, summary: &ReplaySummary) -> Result<(), ReplayError> {
+    // 4.0 [Decompressed data] starts with an unknown dword
+    write_dword(data, 0x00000110);
+
+    // 4.1 [PlayerRecord]
+    data.push(if summary.record_player_is_host { 0x00 } else { 0x16 });
+    data.push(summary.record_player_id);
+    let record_player = summary.players.get(&summary.record_player_id);
+    write_nullterminated_string(data, record_player.map(|p| p.battle_tag.as_str()).unwrap_or_default());
+    write_additional_data(data, record_player)?;
+
+    // 4.2 [GameName], followed by an empty string
+    write_nullterminated_string(data, &summary.game_name);
+    data.push(0x00);
+
+    // 4.3 [Encoded String]
+    data.append(&mut encode_gamesettings(&game_settings_bytes(summary)));
+
+    // 4.6 [PlayerCount]
+    let info = &summary.game_start_info;
+    write_dword(data, info.num_players_slots);
+
+    // 4.7 [GameType]
+    data.push(info.game_type.to_u8());
+    let private_byte = match (info.is_private_custom_game, info.game_type_raw[0]) {
+        (true, _) => 0x08,
+        (false, 0x08) => 0x00,
+        (false, byte) => byte
+    };
+    data.push(private_byte);
+    data.extend_from_slice(&info.game_type_raw[1..]);
+
+    // 4.8 [LanguageID?]
+    write_dword(data, info.language_id);
+
+    // 4.9 [PlayerList]
+    let mut player_ids: Vec<u8> = summary.players.keys().copied().collect();
+    player_ids.sort();
+    for player_id in player_ids.iter().filter(|id| **id != summary.record_player_id) {
+        data.push(0x16);
+        data.push(*player_id);
+        let player = &summary.players[player_id];
+        write_nullterminated_string(data, &player.battle_tag);
+        write_additional_data(data, Some(player))?;
+        write_dword(data, player.record_unknown);
+    }
+
+    // Reforged player metadata, profiles first
+    let metadata: Vec<&ReforgedPlayerMetadata> = player_ids.iter().filter_map(|id| summary.players[id].reforged.as_ref()).collect();
+    let profiles = metadata.iter().filter(|m| m.has_profile()).map(|m| m.to_profile());
+    let other_records = metadata.iter().flat_map(|m| m.other_records.iter().cloned());
+    for record in profiles.chain(other_records).chain(summary.reforged_records.iter().cloned()) {
+        let record_data = record.encode();
+        data.push(0x39);
+        data.push(record.subtype);
+        write_dword(data, checked_u32("Reforged metadata record length", record_data.len())?);
+        data.extend_from_slice(&record_data);
+    }
+
+    // 4.10 [GameStartRecord]
+    let mut game_start_record: Vec<u8> = vec![checked_u8("slot count", summary.slots.len())?];
+    // 4.11 [SlotRecord]
+    for slot in &summary.slots {
+        game_start_record.push(slot.player_id);
+        game_start_record.push(slot.map_download_percent);
+        game_start_record.push(match slot.status {
+            SlotStatus::UNKNOWN => slot.raw[2],
+            status => status as u8
+        });
+        game_start_record.push(match slot.is_computer == (slot.raw[3] == 1) {
+            true => slot.raw[3],
+            false => slot.is_computer as u8
+        });
+        game_start_record.push(slot.team_index);
+        game_start_record.push(match slot.color {
+            SlotColor::UNKNOWN => slot.raw[5],
+            color => color as u8 - 1
+        });
+        game_start_record.push(slot.race_flags);
+        game_start_record.push(match slot.ai_strength {
+            ComputerAIStrength::UNKNOWN => slot.raw[7],
+            ai_strength => ai_strength as u8
+        });
+        game_start_record.push(slot.handicap_percent);
+    }
+    write_dword(&mut game_start_record, info.random_seed);
+    game_start_record.push(info.selection_mode.to_u8());
+    game_start_record.push(info.start_spot_count);
+    data.push(0x19);
+    write_word(data, checked_u16("GameStartRecord length", game_start_record.len())?);
+    data.append(&mut game_start_record);
+    return Ok(());
+}
+
+// Text of a chat message after the filter, None if it is dropped
+fn filtered_text(filter: &mut ChatFilter<'_>, message: &ChatMessage) -> Option<String> {
+    return match filter(message) {
+        ChatFilterResult::Keep => Some(message.message.clone()),
+        ChatFilterResult::Drop => None,
+        ChatFilterResult::Replace(text) => Some(text)
+    };
+}
+
+// 5.0 [ReplayData]
+fn write_record(data: &mut Vec<u8>, record: &ReplayRecord, filter: &mut ChatFilter<'_>) -> Result<(), ReplayError> {
+    match record {
+        ReplayRecord::Startup(startup_record) => {
+            data.push(startup_record.record_type as u8);
+            write_dword(data, startup_record.value);
+        },
+        ReplayRecord::LeaveGame(leave) => {
+            data.push(0x17);
+            write_dword(data, leave.reason.to_u32());
+            data.push(leave.player_id);
+            write_dword(data, leave.result);
+            write_dword(data, leave.unknown);
+        },
+        ReplayRecord::TimeSlot(time_slot) => {
+            let mut command_blocks: Vec<u8> = vec![];
+            for command_block in &time_slot.command_blocks {
+                let mut actions: Vec<u8> = vec![];
+                for action in &command_block.actions {
+                    let ActionKind::TriggerChat { unknown_a, unknown_b, message } = &action.kind else {
+                        write_action(&mut actions, &action.kind)?;
+                        continue;
+                    };
+                    let trigger_chat = ChatMessage {
+                        sender_player_id: action.player_id,
+                        channel: ChatChannel::Trigger,
+                        recipient_player_id: None,
+                        flag: None,
+                        message: message.clone(),
+                        timestamp: action.timestamp
+                    };
+                    if let Some(text) = filtered_text(filter, &trigger_chat) {
+                        write_action(&mut actions, &ActionKind::TriggerChat { unknown_a: *unknown_a, unknown_b: *unknown_b, message: text })?;
+                    }
+                }
+                write_command_block(&mut command_blocks, command_block.player_id, &actions)?;
+            }
+            write_time_slot(data, time_slot.increment, &command_blocks)?;
+        },
+        ReplayRecord::Other(other_record) => {
+            data.push(other_record.id);
+            data.extend_from_slice(&other_record.data);
+        },
+        ReplayRecord::Chat(message) => {
+            if message.channel == ChatChannel::Trigger {
+                warn!("Trigger chat is written with the actions of its TimeSlot, skipping chat record: {:?}", message);
+                return Ok(());
+            }
+            if let Some(text) = filtered_text(filter, message) {
+                write_chat(data, message, &text)?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+// 0x20 [ChatMessage]
+fn write_chat(data: &mut Vec<u8>, message: &ChatMessage, text: &str) -> Result<(), ReplayError> {
+    let mut body: Vec<u8> = vec![];
+    let mode = match message.channel {
+        ChatChannel::All => Some(0x00),
+        ChatChannel::Allies => Some(0x01),
+        ChatChannel::Observers => Some(0x02),
+        ChatChannel::Private(slot) => Some(slot as u32 + 3),
+        ChatChannel::Unknown(mode) => Some(mode),
+        ChatChannel::Lobby | ChatChannel::Trigger => None
+    };
+    // Startup screen messages have no chat mode
+    match mode {
+        Some(mode) => {
+            body.push(message.flag.unwrap_or(0x20));
+            write_dword(&mut body, mode);
+        },
+        None => body.push(0x10)
+    }
+    write_nullterminated_string(&mut body, text);
+    data.push(0x20);
+    data.push(message.sender_player_id);
+    write_word(data, checked_u16("chat message length", body.len())?);
+    data.append(&mut body);
+    return Ok(());
+}
+
+// 0x1F [TimeSlot]
+fn write_time_slot(data: &mut Vec<u8>, increment: u16, command_blocks: &[u8]) -> Result<(), ReplayError> {
+    data.push(0x1F);
+    write_word(data, checked_u16("TimeSlot length", command_blocks.len() + 2)?);
+    write_word(data, increment);
+    data.extend_from_slice(command_blocks);
+    return Ok(());
+}
+
+fn write_command_block(data: &mut Vec<u8>, player_id: u8, actions: &[u8]) -> Result<(), ReplayError> {
+    data.push(player_id);
+    write_word(data, checked_u16("command block length", actions.len())?);
+    data.extend_from_slice(actions);
+    return Ok(());
+}
+
+fn write_action(data: &mut Vec<u8>, kind: &ActionKind) -> Result<(), ReplayError> {
+    data.push(kind.action_type() as u8);
+    match kind {
+        ActionKind::Pause
+        | ActionKind::Resume
+        | ActionKind::IncreaseGameSpeed
+        | ActionKind::DecreaseGameSpeed
+        | ActionKind::PreSubselection
+        | ActionKind::Cheat { .. }
+        | ActionKind::EscPressed
+        | ActionKind::ChooseHeroSkillSubmenu
+        | ActionKind::ChooseBuildingSubmenu => {},
+        ActionKind::SetGameSpeed { speed } => data.push(*speed),
+        ActionKind::SaveGame { name } => write_nullterminated_string(data, name),
+        ActionKind::SaveGameDone { unknown } => write_dword(data, *unknown),
+        ActionKind::AbilityBasic { flags, item_id, unknown_a, unknown_b } => {
+            write_word(data, *flags);
+            write_ability_itemid(data, item_id);
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+        },
+        ActionKind::AbilityWithTargetLocation { flags, item_id, unknown_a, unknown_b, location } => {
+            write_word(data, *flags);
+            write_ability_itemid(data, item_id);
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_location(data, location);
+        },
+        ActionKind::AbilityWithTargetObject { flags, item_id, unknown_a, unknown_b, location, target } => {
+            write_word(data, *flags);
+            write_ability_itemid(data, item_id);
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_location(data, location);
+            write_object_ids(data, target);
+        },
+        ActionKind::ItemTransfer { flags, item_id, unknown_a, unknown_b, location, target, item } => {
+            write_word(data, *flags);
+            write_ability_itemid(data, item_id);
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_location(data, location);
+            write_object_ids(data, target);
+            write_object_ids(data, item);
+        },
+        ActionKind::AbilityWithTwoTargetLocations { flags, item_id, unknown_a, unknown_b, location, item_id_b, unknown, location_b } => {
+            write_word(data, *flags);
+            write_ability_itemid(data, item_id);
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_location(data, location);
+            write_ability_itemid(data, item_id_b);
+            data.extend_from_slice(unknown);
+            write_location(data, location_b);
+        },
+        ActionKind::ChangeSelection { mode, objects } => {
+            data.push(*mode as u8);
+            write_word(data, checked_u16("selected object count", objects.len())?);
+            for object in objects {
+                write_object_ids(data, object);
+            }
+        },
+        ActionKind::GroupAssign { group, objects } => {
+            data.push(*group);
+            write_word(data, checked_u16("control group object count", objects.len())?);
+            for object in objects {
+                write_object_ids(data, object);
+            }
+        },
+        ActionKind::GroupSelect { group, unknown } => {
+            data.push(*group);
+            data.push(*unknown);
+        },
+        ActionKind::SelectSubgroup { item_id, object } => {
+            write_ability_itemid(data, item_id);
+            write_object_ids(data, object);
+        },
+        ActionKind::Unknown1B { unknown, object } => {
+            data.push(*unknown);
+            write_object_ids(data, object);
+        },
+        ActionKind::SelectGroundItem { flags, object } => {
+            data.push(*flags);
+            write_object_ids(data, object);
+        },
+        ActionKind::CancelHeroRevival { hero } => write_object_ids(data, hero),
+        ActionKind::RemoveFromQueue { slot, item_id } => {
+            data.push(*slot);
+            write_ability_itemid(data, item_id);
+        },
+        ActionKind::Unknown21 { unknown_a, unknown_b } => {
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+        },
+        ActionKind::CheatResources { unknown, amount, .. } => {
+            data.push(*unknown);
+            write_dword(data, *amount);
+        },
+        ActionKind::CheatDaylightSavings { time_of_day } => write_dword_float(data, *time_of_day),
+        ActionKind::ChangeAllyOptions { slot, flags } => {
+            data.push(*slot);
+            write_dword(data, *flags);
+        },
+        ActionKind::TransferResources { slot, gold, lumber } => {
+            data.push(*slot);
+            write_dword(data, *gold);
+            write_dword(data, *lumber);
+        },
+        ActionKind::TriggerChat { unknown_a, unknown_b, message } => {
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_nullterminated_string(data, message);
+        },
+        ActionKind::ScenarioTrigger { unknown_a, unknown_b, unknown_c } => {
+            write_dword(data, *unknown_a);
+            write_dword(data, *unknown_b);
+            write_dword(data, *unknown_c);
+        },
+        ActionKind::MinimapSignal { location, unknown } => {
+            write_location(data, location);
+            write_dword(data, *unknown);
+        },
+        ActionKind::ContinueGameB { unknown } | ActionKind::ContinueGameA { unknown } | ActionKind::Unknown7B { unknown } => {
+            for dword in unknown {
+                write_dword(data, *dword);
+            }
+        },
+        ActionKind::SyncStoreInteger { filename, mission_key, key, value } | ActionKind::SyncStoreUnknown6D { filename, mission_key, key, value } => {
+            write_nullterminated_string(data, filename);
+            write_nullterminated_string(data, mission_key);
+            write_nullterminated_string(data, key);
+            write_dword(data, *value);
+        },
+        ActionKind::SyncStoreUnknown6C { filename, mission_key, key } => {
+            write_nullterminated_string(data, filename);
+            write_nullterminated_string(data, mission_key);
+            write_nullterminated_string(data, key);
+        },
+        ActionKind::ArrowKey { key } => data.push(*key),
+        ActionKind::Unknown7A { unknown } => {
+            for dword in unknown {
+                write_dword(data, *dword);
+            }
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(battle_tag: &str, additional_data: Vec<u8>, record_unknown: u32) -> ReplayPlayer {
+        return ReplayPlayer {
+            battle_tag: String::from(battle_tag),
+            leave_reason: LeaveReason::UNKNOWN(0),
+            result_byte: 0,
+            left_at: 0,
+            reforged: None,
+            additional_data,
+            record_unknown
+        };
+    }
+
+    fn slot(player_id: u8, color: SlotColor, raw: [u8; 9]) -> Slot {
+        return Slot {
+            player_id,
+            map_download_percent: 100,
+            status: SlotStatus::OCCUPIED,
+            is_computer: false,
+            team_index: player_id - 1,
+            color,
+            race: SlotRace::UNKNOWN,
+            race_flags: 0x21,
+            ai_strength: ComputerAIStrength::UNKNOWN,
+            handicap_percent: 100,
+            raw
+        };
+    }
+
+    // Uses values the decoder does not know wherever it keeps the original value
+    fn summary() -> ReplaySummary {
+        let mut players: HashMap<u8, ReplayPlayer> = HashMap::new();
+        let mut alice = player("Alice#123", vec![0x01, 0x00], 0);
+        alice.reforged = Some(ReforgedPlayerMetadata {
+            player_id: 1,
+            battle_tag: String::from("Alice#123"),
+            clan: String::from("OZE"),
+            portrait: String::from("p001"),
+            unknown_fields: vec![ProtobufField { number: 5, value: ProtobufValue::Varint(7) }],
+            other_records: vec![ReforgedRecord {
+                subtype: 0x04,
+                fields: vec![ProtobufField { number: 1, value: ProtobufValue::Varint(1) }, ProtobufField { number: 2, value: ProtobufValue::Fixed32(9) }],
+                raw_data: None
+            }]
+        });
+        players.insert(1, alice);
+        players.insert(2, player("Bob#456", vec![0x04, 0x01, 0x02, 0x03], 0x99));
+        return ReplaySummary {
+            version: 1,
+            header: ReplayHeader {
+                magic: String::from("Warcraft III recorded game"),
+                header_size: 68,
+                compressed_size: 0,
+                header_version: 1,
+                decompressed_size: 0,
+                num_data_blocks: 0,
+                product_id: String::from("W3XP"),
+                patch_version: 10032,
+                build_number: 6114,
+                flags: 0x8000,
+                duration_ms: 0,
+                crc32: 0
+            },
+            record_player_id: 1,
+            record_player_is_host: true,
+            game_name: String::from("OZE test game"),
+            map_name: String::from("Maps\\Test.w3x"),
+            game_creator_battle_tag: String::from("Alice#123"),
+            game_settings: GameSettings {
+                game_speed: 2,
+                vis_default: true,
+                obs_mode: 3,
+                fixed_teams: 3,
+                random_races: true,
+                map_checksum: 0x78563412,
+                extra_data: (0..20).collect(),
+                raw: [0x00, 0x80, 0x00, 0x80, 0x12, 0x34, 0x56, 0x78, 0x9A],
+                ..Default::default()
+            },
+            game_start_info: GameStartInfo {
+                num_players_slots: 2,
+                game_type: GameType::UNKNOWN(0x42),
+                is_private_custom_game: false,
+                language_id: 0x18F345,
+                random_seed: 0xDEADBEEF,
+                selection_mode: SlotSelectionMode::UNKNOWN(0x77),
+                start_spot_count: 2,
+                game_type_raw: [0x05, 0x11, 0x22]
+            },
+            slots: vec![
+                slot(1, SlotColor::RED, [0, 0, 0, 0, 0, 0, 0, 0x64, 0]),
+                slot(2, SlotColor::UNKNOWN, [0, 0, 0, 0, 0, 0x30, 0, 0x64, 0])
+            ],
+            players,
+            reforged_records: vec![ReforgedRecord { subtype: 0x05, fields: vec![], raw_data: Some(vec![0xFF, 0xFF]) }]
+        };
+    }
+
+    fn records() -> Vec<ReplayRecord> {
+        let action = |kind: ActionKind| Action { player_id: 2, timestamp: 350, kind, units: None };
+        return vec![
+            ReplayRecord::Startup(StartupRecord { record_type: StartupRecordType::FIRST, value: 1, timestamp: 0 }),
+            ReplayRecord::Chat(ChatMessage {
+                sender_player_id: 1,
+                channel: ChatChannel::Lobby,
+                recipient_player_id: None,
+                flag: Some(0x10),
+                message: String::from("gl"),
+                timestamp: 0
+            }),
+            // Command blocks without actions are kept
+            ReplayRecord::TimeSlot(TimeSlot { timestamp: 250, increment: 250, command_blocks: vec![CommandBlock { player_id: 1, actions: vec![] }] }),
+            ReplayRecord::TimeSlot(TimeSlot { timestamp: 350, increment: 100, command_blocks: vec![CommandBlock {
+                player_id: 2,
+                actions: vec![
+                    action(ActionKind::TriggerChat { unknown_a: 7, unknown_b: 0x1234, message: String::from("-stats") }),
+                    action(ActionKind::ChangeAllyOptions { slot: 0, flags: 0x1F })
+                ]
+            }] }),
+            ReplayRecord::Chat(ChatMessage {
+                sender_player_id: 2,
+                channel: ChatChannel::Unknown(0x0100),
+                recipient_player_id: None,
+                flag: Some(0x20),
+                message: String::from("gg"),
+                timestamp: 350
+            }),
+            ReplayRecord::Other(OtherRecord { id: 0x22, data: vec![0x04, 0x07, 0x00, 0x00, 0x00], timestamp: 350 }),
+            ReplayRecord::Other(OtherRecord { id: 0x2F, data: vec![0x01, 0x00, 0x00, 0x00, 0x1E, 0x00, 0x00, 0x00], timestamp: 350 }),
+            ReplayRecord::LeaveGame(PlayerLeave { player_id: 2, reason: LeaveReason::UNKNOWN(0x0D), result: 0x09, unknown: 0x55, timestamp: 350 }),
+            ReplayRecord::LeaveGame(PlayerLeave { player_id: 1, reason: LeaveReason::CONNECTION_CLOSED_BY_LOCAL_GAME, result: 0x0A, unknown: 0x01, timestamp: 350 })
+        ];
+    }
+
+    fn write(summary: &ReplaySummary, records: &[ReplayRecord]) -> Vec<u8> {
+        let mut writer = ReplayWriter::new(summary).unwrap();
+        for record in records {
+            writer.write_record(record).unwrap();
+        }
+        return writer.into_bytes().unwrap();
+    }
+
+    fn decode(bytes: &[u8], options: &ParseOptions) -> (ReplaySummary, Vec<ReplayRecord>, Option<RawReplay>) {
+        let mut decoder = ReplayDecoder::with_options(bytes, options).unwrap();
+        let mut records: Vec<ReplayRecord> = vec![];
+        while let Some(record) = decoder.next_record().unwrap() {
+            records.push(record);
+        }
+        let raw = decoder.take_raw();
+        return (decoder.into_summary(), records, raw);
+    }
+
+    // The header describes the written blocks, so it is left out
+    fn summary_json(summary: &ReplaySummary) -> serde_json::Value {
+        let mut value = serde_json::to_value(summary).unwrap();
+        value.as_object_mut().unwrap().remove("header");
+        return value;
+    }
+
+    #[test]
+    fn decoder_reads_what_writer_wrote() {
+        let (summary, records) = (summary(), records());
+        let bytes = write(&summary, &records);
+        let (decoded_summary, decoded_records, _) = decode(&bytes, &ParseOptions { verify_checksums: true, ..Default::default() });
+        assert_eq!(summary_json(&decoded_summary), summary_json(&summary));
+        assert_eq!(format!("{:?}", decoded_records), format!("{:?}", records));
+
+        // The values kept for writing but not serialized survive as well
+        assert_eq!(write(&decoded_summary, &decoded_records), bytes);
+    }
+
+    #[test]
+    fn parsed_replay_is_written_back() {
+        let bytes = write(&summary(), &records());
+        let replay = Replay::try_from_bytes(&bytes).unwrap();
+        let mut written: Vec<u8> = vec![];
+        replay.write_to(&mut written).unwrap();
+
+        // Trigger chat moves behind the other actions of its command block, which the Replay does not tell apart
+        let written_replay = Replay::try_from_bytes_with_options(&written, &ParseOptions { verify_checksums: true, ..Default::default() }).unwrap();
+        let json = |replay: &Replay| {
+            let mut value = serde_json::to_value(replay).unwrap();
+            value.as_object_mut().unwrap().remove("header");
+            value
+        };
+        assert_eq!(json(&written_replay), json(&replay));
+        assert_eq!(written_replay.header.duration_ms, 350);
+        assert_eq!(format!("{:?}", written_replay.time_slots), format!("{:?}", replay.time_slots));
+        assert_eq!(format!("{:?}", written_replay.other_records), format!("{:?}", replay.other_records));
+
+        let mut rewritten: Vec<u8> = vec![];
+        written_replay.write_to(&mut rewritten).unwrap();
+        assert_eq!(rewritten, written);
+    }
+
+    #[test]
+    fn header_version_0_is_kept() {
+        let mut summary = summary();
+        summary.version = 0;
+        summary.header.header_version = 0;
+        summary.header.header_size = 64;
+        summary.header.product_id = String::from("WAR3");
+        summary.header.patch_version = 26;
+        let bytes = write(&summary, &records());
+        assert_eq!((parse_dword(&bytes[0x1C..0x20]), parse_dword(&bytes[0x24..0x28])), (64, 0));
+
+        let (decoded_summary, decoded_records, _) = decode(&bytes, &ParseOptions { verify_checksums: true, ..Default::default() });
+        let header = &decoded_summary.header;
+        assert_eq!((header.header_version, header.header_size, header.patch_version, header.build_number), (0, 64, 26, 6114));
+        assert_eq!(summary_json(&decoded_summary), summary_json(&summary));
+        assert_eq!(format!("{:?}", decoded_records), format!("{:?}", records()));
+        assert_eq!(write(&decoded_summary, &decoded_records), bytes);
+    }
+
+    #[test]
+    fn reforged_record_crosses_block_boundary() {
+        let mut summary = summary();
+        summary.reforged_records = vec![ReforgedRecord { subtype: 0x05, fields: vec![], raw_data: Some(vec![0xFF; BLOCK_SIZE + 100]) }];
+        let bytes = write(&summary, &records());
+        let (decoded_summary, decoded_records, _) = decode(&bytes, &ParseOptions::default());
+        assert_eq!(decoded_summary.reforged_records[0].raw_data, summary.reforged_records[0].raw_data);
+        assert_eq!(format!("{:?}", decoded_records), format!("{:?}", records()));
+    }
+
+    #[test]
+    fn game_starts_with_the_first_actions() {
+        let bytes = write(&summary(), &records());
+        let replay = Replay::try_from_bytes(&bytes).unwrap();
+        assert_eq!(replay.game_start, 250);
+        assert_eq!(replay.game_time(350), 100);
+    }
+
+    #[test]
+    fn from_raw_reproduces_the_data() {
+        let bytes = write(&summary(), &records());
+        let options = ParseOptions { keep_raw_records: true, ..Default::default() };
+        let (_, records, raw) = decode(&bytes, &options);
+        let raw = raw.unwrap();
+        assert_eq!(raw.records.len(), records.len());
+        for (record, raw_record) in records.iter().zip(raw.records.iter()) {
+            assert_eq!(ReplayWriter::encode_record(record).unwrap(), raw_record.data);
+        }
+        assert_eq!(ReplayWriter::from_raw(&raw).unwrap().into_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn chat_filter_borrows_caller_data() {
+        let banned = vec![String::from("gg")];
+        let mut filtered: Vec<String> = vec![];
+        let mut writer = ReplayWriter::new(&summary()).unwrap();
+        writer.set_chat_filter(|message| match banned.contains(&message.message) {
+            true => {
+                filtered.push(message.message.clone());
+                ChatFilterResult::Drop
+            },
+            false => ChatFilterResult::Keep
+        });
+        for record in records() {
+            writer.write_record(&record).unwrap();
+        }
+        let bytes = writer.into_bytes().unwrap();
+        assert_eq!(filtered, banned);
+
+        let (_, records, _) = decode(&bytes, &ParseOptions::default());
+        let chat: Vec<&str> = records.iter().filter_map(|r| match r {
+            ReplayRecord::Chat(message) => Some(message.message.as_str()),
+            _ => None
+        }).collect();
+        assert_eq!(chat, ["gl"]);
+    }
+
+    #[test]
+    fn lengths_exceeding_their_field_are_errors() {
+        let long_message = "a".repeat(u16::MAX as usize);
+        let chat = ReplayRecord::Chat(ChatMessage {
+            sender_player_id: 1,
+            channel: ChatChannel::All,
+            recipient_player_id: None,
+            flag: Some(0x20),
+            message: long_message.clone(),
+            timestamp: 0
+        });
+        assert!(matches!(ReplayWriter::encode_record(&chat), Err(ReplayError::ValueTooLarge { field: "chat message length", .. })));
+
+        let trigger_chat = ActionKind::TriggerChat { unknown_a: 0, unknown_b: 0, message: long_message };
+        let time_slot = ReplayRecord::TimeSlot(TimeSlot {
+            timestamp: 0,
+            increment: 100,
+            command_blocks: vec![CommandBlock { player_id: 1, actions: vec![Action { player_id: 1, timestamp: 0, kind: trigger_chat, units: None }] }]
+        });
+        let mut writer = ReplayWriter::new(&summary()).unwrap();
+        assert!(matches!(writer.write_record(&time_slot), Err(ReplayError::ValueTooLarge { field: "command block length", .. })));
+    }
+
+    #[test]
+    fn gamesettings_encoding_is_inverse() {
+        let settings: Vec<u8> = (1..=255u8).chain(1..=30u8).collect();
+        let encoded = encode_gamesettings(&settings);
+        assert!(!encoded[..encoded.len() - 1].contains(&0x00));
+        assert_eq!(decode_gamesettings(&encoded), settings);
+        assert_eq!(encode_gamesettings(&decode_gamesettings(&encoded)), encoded);
+    }
+}